@@ -0,0 +1,145 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rust_decimal::Decimal;
+use std::sync::Arc;
+use toy_engine::account::{
+    Account, Generator, GeneratorOptions, Input, InputOptions, OutputOptions, Policy, Transaction,
+    TransactionType,
+};
+
+/// Generates transaction CSV with the default mix of rows.
+fn generate(clients: u64, rows: u64) -> Vec<u8> {
+    generate_with(GeneratorOptions {
+        clients,
+        rows,
+        ..GeneratorOptions::default()
+    })
+}
+
+fn generate_with(options: GeneratorOptions) -> Vec<u8> {
+    let mut data = Vec::new();
+    Generator::new(options)
+        .unwrap()
+        .write_csv(&mut data)
+        .unwrap();
+    data
+}
+
+/// Reads and renders every account, discarding the output.
+fn render(data: &[u8]) {
+    let input = Input::from_csv_data(data, &InputOptions::default()).unwrap();
+    Account::accounts_state_from_input(
+        input,
+        &Arc::new(Policy::default()),
+        &OutputOptions::default(),
+        std::io::sink(),
+    )
+    .unwrap();
+}
+
+fn transaction(transaction_type: TransactionType, tx: u64) -> Transaction {
+    Transaction {
+        transaction_type,
+        tx: tx.into(),
+        client: 1.into(),
+        timestamp: None,
+        currency: None,
+    }
+}
+
+/// An account with the provided number of deposits in its history.
+fn account_with_history(length: u64) -> Account {
+    let mut account = Account::new(1.into());
+    for tx in 1..=length {
+        account
+            .resolve_new_transaction(transaction(
+                TransactionType::Deposit(Decimal::new(1, 0)),
+                tx,
+            ))
+            .unwrap();
+    }
+    account
+}
+
+/// Throughput of reading CSV into transactions.
+fn parsing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parsing");
+    for rows in [10_000, 100_000] {
+        let data = generate(1_000, rows);
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(rows), &data, |b, data| {
+            b.iter(|| Input::from_csv_data(data, &InputOptions::default()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// Cost of applying a single transaction to an account as its history grows. Deposits check the
+/// history for duplicates, and disputes and resolves look up the transaction they reference.
+fn apply(c: &mut Criterion) {
+    let mut group = c.benchmark_group("apply");
+    for length in [1_000, 10_000, 100_000] {
+        group.bench_function(BenchmarkId::new("deposit", length), |b| {
+            let mut account = account_with_history(length);
+            let mut tx = length;
+            b.iter(|| {
+                tx += 1;
+                account
+                    .resolve_new_transaction(transaction(
+                        TransactionType::Deposit(Decimal::new(1, 0)),
+                        tx,
+                    ))
+                    .unwrap()
+            })
+        });
+        group.bench_function(BenchmarkId::new("dispute_and_resolve", length), |b| {
+            let mut account = account_with_history(length);
+            b.iter(|| {
+                account
+                    .resolve_new_transaction(transaction(TransactionType::Dispute(None), 1))
+                    .unwrap();
+                account
+                    .resolve_new_transaction(transaction(TransactionType::Resolve(None), 1))
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// End-to-end rendering of data where a large share of rows dispute, resolve or charge back
+/// earlier deposits.
+fn disputes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("disputes");
+    let rows = 100_000;
+    let data = generate_with(GeneratorOptions {
+        clients: 100,
+        rows,
+        dispute_rate: 0.3,
+        resolve_rate: 0.2,
+        chargeback_rate: 0.01,
+        ..GeneratorOptions::default()
+    });
+    group.throughput(Throughput::Elements(rows));
+    group.bench_function(BenchmarkId::from_parameter(rows), |b| {
+        b.iter(|| render(&data))
+    });
+    group.finish();
+}
+
+/// End-to-end rendering of large files. The data is generated only for the benchmarks that run,
+/// so `cargo bench -- render/1000000` skips the ten million row file.
+fn rendering(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render");
+    group.sample_size(10);
+    for rows in [1_000_000, 10_000_000] {
+        group.throughput(Throughput::Elements(rows));
+        group.bench_function(BenchmarkId::from_parameter(rows), |b| {
+            let data = generate(10_000, rows);
+            b.iter(|| render(&data))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parsing, apply, disputes, rendering);
+criterion_main!(benches);
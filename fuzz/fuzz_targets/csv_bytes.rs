@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// Arbitrary bytes, seeded with the CSV files in test_data/
+fuzz_target!(|data: &[u8]| {
+    toy_engine_fuzz::check_pipeline(data);
+});
@@ -0,0 +1,101 @@
+#![no_main]
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rust_decimal::Decimal;
+use toy_engine::account::TransactionType;
+
+const HEADERS: [&str; 8] = [
+    "type",
+    "client",
+    "tx",
+    "amount",
+    "timestamp",
+    "currency",
+    "destination",
+    "reference",
+];
+
+#[derive(Debug, Arbitrary)]
+enum Kind {
+    Deposit,
+    Withdraw,
+    Transfer,
+    Fee,
+    Interest,
+    Adjustment,
+    Authorize,
+    Capture,
+    Void,
+    Dispute,
+    Resolve,
+    Chargeback,
+    Unlock,
+    Close,
+    Reopen,
+    Other(String),
+}
+
+/// A row that looks like a transaction, with small identifiers so that disputes and transfers
+/// often reference real transactions and clients.
+#[derive(Debug, Arbitrary)]
+struct Row {
+    kind: Kind,
+    client: u8,
+    tx: u8,
+    amount: Option<(i64, u8)>,
+    timestamp: Option<u16>,
+    currency: Option<bool>,
+    destination: Option<u8>,
+    reference: Option<u8>,
+}
+
+impl Row {
+    fn fields(&self) -> Vec<String> {
+        let kind = match &self.kind {
+            Kind::Other(other) => other.clone(),
+            kind => format!("{:?}", kind).to_lowercase(),
+        };
+        let amount = self.amount.map_or(String::new(), |(mantissa, scale)| {
+            Decimal::new(mantissa, u32::from(scale % 29)).to_string()
+        });
+        let timestamp = self.timestamp.map_or(String::new(), |minutes| {
+            format!(
+                "2021-01-{:02}T{:02}:{:02}:00Z",
+                1 + minutes / 1440 % 28,
+                minutes / 60 % 24,
+                minutes % 60
+            )
+        });
+        let currency = match self.currency {
+            Some(true) => String::from("USD"),
+            Some(false) => String::from("EUR"),
+            None => String::new(),
+        };
+        let optional = |value: Option<u8>| value.map_or(String::new(), |value| value.to_string());
+        vec![
+            kind,
+            (self.client % 4).to_string(),
+            self.tx.to_string(),
+            amount,
+            timestamp,
+            currency,
+            optional(self.destination.map(|destination| destination % 4)),
+            optional(self.reference),
+        ]
+    }
+}
+
+// Structured rows rendered into CSV, so that most inputs reach the account state machine
+fuzz_target!(|rows: Vec<Row>| {
+    let headers = csv::StringRecord::from(HEADERS.to_vec());
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&headers).unwrap();
+    for row in &rows {
+        let record = csv::StringRecord::from(row.fields());
+        // Parsing a single record must fail with an error rather than a panic
+        let _ = TransactionType::from_record(&headers, &record);
+        writer.write_record(&record).unwrap();
+    }
+    let data = writer.into_inner().unwrap();
+    toy_engine_fuzz::check_pipeline(&data);
+});
@@ -0,0 +1,64 @@
+use chrono::Duration;
+use std::sync::Arc;
+use toy_engine::account::{
+    Account, Input, InputOptions, Ledger, OutputOptions, Policy, TransactionSet,
+};
+
+/// Runs CSV data through every stage of the pipeline, from parsing to rendered output, and panics
+/// when a stage misbehaves. Data that is not CSV at all is expected to be rejected with an error.
+///
+/// # Arguments
+///
+/// * `data` - Untrusted CSV data.
+pub fn check_pipeline(data: &[u8]) {
+    let rows = data.iter().filter(|byte| **byte == b'\n').count() + 1;
+
+    // Parsing must keep at most one transaction per row, both in file order and reordered
+    for options in [
+        InputOptions::default(),
+        InputOptions {
+            as_of: None,
+            lateness: Some(Duration::minutes(5)),
+        },
+    ] {
+        let input = match Input::from_csv_data(data, &options) {
+            Ok(input) => input,
+            Err(_) => return,
+        };
+        assert!(input.transactions.len() + input.late.len() <= rows);
+
+        // Every applied transaction must leave the balances consistent
+        let transactions = input.transactions.len();
+        let mut ledger = Ledger::new(Arc::new(Policy::default())).paranoid(true);
+        for transaction in input.transactions.clone() {
+            ledger.apply(transaction);
+        }
+        if let Some(violation) = ledger.violations().first() {
+            panic!("{}", violation);
+        }
+        // A transfer may open an account for both of its clients
+        assert!(ledger.into_accounts().count() <= 2 * transactions);
+
+        let mut output = Vec::new();
+        let options = OutputOptions {
+            paranoid: true,
+            ..OutputOptions::default()
+        };
+        Account::accounts_state_from_input(
+            input,
+            &Arc::new(Policy::default()),
+            &options,
+            &mut output,
+        )
+        .expect("Failed to render accounts.");
+        assert!(output.iter().filter(|byte| **byte == b'\n').count() <= 2 * transactions + 2);
+    }
+
+    // Rendering whole TransactionSets must agree on the invariants as well
+    if let Ok(sets) = TransactionSet::transaction_sets_from_csv_data(data) {
+        for set in sets {
+            let balance = Account::from_transaction_set(set).balance();
+            assert_eq!(balance.invariant_violation(), None);
+        }
+    }
+}
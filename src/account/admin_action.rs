@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+/// Who performed an administrative transaction and why. Recorded in the account history with the
+/// transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdminAction {
+    /// Identifier of the operator who performed the action.
+    pub operator: String,
+    /// Why the action was performed, if given.
+    pub reason: Option<String>,
+}
+
+impl AdminAction {
+    /// Generate an AdminAction from the `operator` and `reason` columns of a CSV record. The
+    /// operator is required.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - Header data from the CSV file. This is used to dynamically access the record
+    ///   data regardless of column order. It only depends on the column names being known and
+    ///   consistent.
+    /// * `record` - A StringRecord data row containing an administrative transaction.
+    pub fn from_record(
+        headers: &csv::StringRecord,
+        record: &csv::StringRecord,
+    ) -> Result<AdminAction, String> {
+        let field = |name: &str| match headers.iter().position(|x| x == name) {
+            Some(index) => record.get(index).filter(|value| !value.is_empty()),
+            None => None,
+        };
+        match field("operator") {
+            Some(operator) => Ok(AdminAction {
+                operator: String::from(operator),
+                reason: field("reason").map(String::from),
+            }),
+            None => Err(String::from(
+                "Administrative transactions require an operator.",
+            )),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use super::{Identifier, Transaction};
+use chrono::{DateTime, Utc};
+use std::str::FromStr;
+
+/// A point in the input stream at which rendering stops. Used to view account states as they
+/// stood partway through the input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsOf {
+    /// Include every row up to and including the first row carrying this transaction identifier.
+    Tx(Identifier),
+    /// Include every row up to and including this line of the input. The header is line 1.
+    Line(u64),
+    /// Include every row with a timestamp at or before this time. Rows without a timestamp are
+    /// always included.
+    Timestamp(DateTime<Utc>),
+}
+
+impl AsOf {
+    /// Indicates whether the given input line falls after this point in the stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The line number of a row in the input. The header is line 1.
+    pub fn excludes_line(&self, line: u64) -> bool {
+        match self {
+            AsOf::Line(limit) => line > *limit,
+            _ => false,
+        }
+    }
+
+    /// Indicates whether the given transaction occurred after this point in time.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    pub fn excludes(&self, transaction: &Transaction) -> bool {
+        match (self, transaction.timestamp) {
+            (AsOf::Timestamp(limit), Some(timestamp)) => timestamp > *limit,
+            _ => false,
+        }
+    }
+
+    /// Indicates whether the given transaction is the last one to include.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    pub fn ends_with(&self, transaction: &Transaction) -> bool {
+        match self {
+            AsOf::Tx(tx) => transaction.tx == *tx,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for AsOf {
+    type Err = String;
+
+    /// Parses `tx:<id>`, `line:<number>`, or a timestamp given as `ts:<timestamp>` or as a bare
+    /// RFC 3339 string.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(value) = s.strip_prefix("tx:") {
+            value
+                .trim()
+                .parse::<Identifier>()
+                .map(AsOf::Tx)
+                .map_err(|_| format!("Invalid transaction identifier in as-of point {:?}.", s))
+        } else if let Some(value) = s.strip_prefix("line:") {
+            value
+                .trim()
+                .parse::<u64>()
+                .map(AsOf::Line)
+                .map_err(|_| format!("Invalid line number in as-of point {:?}.", s))
+        } else if let Some(value) = s.strip_prefix("ts:") {
+            Transaction::parse_timestamp(value.trim()).map(AsOf::Timestamp)
+        } else if let Ok(timestamp) = DateTime::parse_from_rfc3339(s) {
+            Ok(AsOf::Timestamp(timestamp.with_timezone(&Utc)))
+        } else {
+            Err(format!(
+                "Unrecognized as-of point {:?}. Expected `tx:<id>`, `line:<number>` or a timestamp.",
+                s
+            ))
+        }
+    }
+}
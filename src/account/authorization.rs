@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::*;
+
+/// An open authorization hold. The authorized amount stays out of the available funds until it
+/// is captured, voided or expires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Authorization {
+    /// The amount still held that has been neither captured nor released.
+    pub remaining: Decimal,
+    /// When the authorization was made, if known.
+    pub opened: Option<DateTime<Utc>>,
+}
+
+impl Authorization {
+    /// Indicates whether any of the authorized amount is still held.
+    pub fn is_open(&self) -> bool {
+        self.remaining > Decimal::zero()
+    }
+}
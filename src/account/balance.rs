@@ -0,0 +1,21 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A point-in-time view of the funds on an Account.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Balance {
+    /// The funds that are available.
+    pub available: Decimal,
+    /// The funds held for disputes and authorizations.
+    pub held: Decimal,
+    /// The part of `held` that is held for authorizations.
+    pub authorized: Decimal,
+    /// The funds in all states.
+    pub total: Decimal,
+    /// Whether the account is locked.
+    pub locked: bool,
+    /// Whether the account is closed.
+    pub closed: bool,
+    /// Whether the available funds or the total are below zero.
+    pub negative: bool,
+}
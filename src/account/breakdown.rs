@@ -0,0 +1,85 @@
+use super::{Identifier, RateTable, TransactionType};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Running totals of the amounts applied to an Account, by category. Used to reconcile revenue
+/// such as fees separately from client funds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Breakdown {
+    /// Sum of applied deposits.
+    pub deposits: Decimal,
+    /// Sum of applied withdrawals.
+    pub withdrawals: Decimal,
+    /// Sum of transfers received from other clients.
+    pub transfers_in: Decimal,
+    /// Sum of transfers sent to other clients.
+    pub transfers_out: Decimal,
+    /// Sum of fees charged.
+    pub fees: Decimal,
+    /// Sum of interest credited.
+    pub interest: Decimal,
+    /// Net sum of adjustments.
+    pub adjustments: Decimal,
+}
+
+impl Default for Breakdown {
+    fn default() -> Self {
+        Breakdown {
+            deposits: Decimal::new(00, 1),
+            withdrawals: Decimal::new(00, 1),
+            transfers_in: Decimal::new(00, 1),
+            transfers_out: Decimal::new(00, 1),
+            fees: Decimal::new(00, 1),
+            interest: Decimal::new(00, 1),
+            adjustments: Decimal::new(00, 1),
+        }
+    }
+}
+
+impl Breakdown {
+    /// Adds an applied transaction to its category. Disputes and administrative transactions do
+    /// not belong to a category.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client identifier of the account the transaction was applied to
+    /// * `transaction_type` - The type of the applied transaction
+    pub fn record(&mut self, client: &Identifier, transaction_type: &TransactionType) {
+        match transaction_type {
+            TransactionType::Deposit(amount) => self.deposits += *amount,
+            TransactionType::Withdraw(amount) => self.withdrawals += *amount,
+            TransactionType::Transfer {
+                destination,
+                amount,
+            } => {
+                if destination == client {
+                    self.transfers_in += *amount;
+                } else {
+                    self.transfers_out += *amount;
+                }
+            }
+            TransactionType::Fee(amount) => self.fees += *amount,
+            TransactionType::Interest(amount) => self.interest += *amount,
+            TransactionType::Adjustment { amount, .. } => self.adjustments += *amount,
+            _ => (),
+        }
+    }
+
+    /// Adds another Breakdown converted at the provided rate to this one. Converted amounts are
+    /// rounded to four decimal places.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The Breakdown to add.
+    /// * `rate` - Units of this Breakdown's currency per unit of the other's.
+    pub fn add_converted(&mut self, other: &Breakdown, rate: Decimal) {
+        let convert = |amount: Decimal| RateTable::convert(amount, rate);
+        self.deposits += convert(other.deposits);
+        self.withdrawals += convert(other.withdrawals);
+        self.transfers_in += convert(other.transfers_in);
+        self.transfers_out += convert(other.transfers_out);
+        self.fees += convert(other.fees);
+        self.interest += convert(other.interest);
+        self.adjustments += convert(other.adjustments);
+    }
+}
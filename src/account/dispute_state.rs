@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::*;
+
+/// The dispute history of a single transaction. A transaction can be disputed in parts, so the
+/// amount under dispute and the amount still open to dispute are tracked separately.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisputeState {
+    /// The amount currently under dispute.
+    pub disputed: Decimal,
+    /// The amount that may still be disputed. Resolved amounts become disputable again, while
+    /// charged back amounts do not.
+    pub disputable: Decimal,
+    /// When the current dispute was opened, if known. A dispute is open while any amount is
+    /// under dispute.
+    pub opened: Option<DateTime<Utc>>,
+}
+
+impl DisputeState {
+    /// Creates the state of a transaction that has not been disputed yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - The amount of the transaction.
+    pub fn new(amount: Decimal) -> DisputeState {
+        DisputeState {
+            disputed: Decimal::new(00, 1),
+            disputable: amount,
+            opened: None,
+        }
+    }
+
+    /// Indicates whether any amount is under dispute.
+    pub fn is_open(&self) -> bool {
+        self.disputed > Decimal::zero()
+    }
+}
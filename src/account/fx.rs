@@ -0,0 +1,85 @@
+use super::Currency;
+use anyhow::{Context, Error, Result};
+use rust_decimal::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Exchange rates between currencies, read from a local file.
+#[derive(Debug, Clone, Default)]
+pub struct RateTable {
+    /// Units of the second currency per unit of the first.
+    rates: HashMap<(Currency, Currency), Decimal>,
+}
+
+/// A single row of a rate table file.
+#[derive(Debug, Deserialize)]
+struct RateRow {
+    from: Currency,
+    to: Currency,
+    rate: Decimal,
+}
+
+/// Balances are converted into a single currency before they are written out.
+#[derive(Debug, Clone)]
+pub struct Conversion {
+    /// The currency to convert into.
+    pub currency: Currency,
+    /// The rates used to convert.
+    pub rates: RateTable,
+}
+
+impl RateTable {
+    /// Reads a rate table from CSV data with `from`, `to` and `rate` columns. Each row gives the
+    /// units of `to` bought by one unit of `from`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Reference to a Vec<u8> of CSV data.
+    pub fn from_csv_data(data: &[u8]) -> Result<RateTable, Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(data);
+        let mut table = RateTable::default();
+        for row in reader.deserialize() {
+            let row: RateRow = row.context("Failed to parse exchange rate row.")?;
+            table.rates.insert((row.from, row.to), row.rate);
+        }
+        Ok(table)
+    }
+
+    /// The units of `to` bought by one unit of `from`. A rate given only in the opposite direction
+    /// is inverted.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The currency converted from.
+    /// * `to` - The currency converted into.
+    pub fn rate(&self, from: &Currency, to: &Currency) -> Option<Decimal> {
+        if from == to {
+            return Some(Decimal::one());
+        }
+        match self.rates.get(&(from.clone(), to.clone())) {
+            Some(rate) => Some(*rate),
+            None => self
+                .rates
+                .get(&(to.clone(), from.clone()))
+                .filter(|rate| !rate.is_zero())
+                .map(|rate| Decimal::one() / *rate),
+        }
+    }
+
+    /// Converts an amount at the provided rate, rounded to four decimal places. Whole results
+    /// keep one decimal place so they are written like the unconverted amounts.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - The amount to convert.
+    /// * `rate` - Units of the target currency per unit of the amount's currency.
+    pub fn convert(amount: Decimal, rate: Decimal) -> Decimal {
+        let mut converted = (amount * rate).round_dp(4);
+        if converted.scale() == 0 {
+            converted.rescale(1);
+        }
+        converted
+    }
+}
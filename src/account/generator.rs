@@ -0,0 +1,311 @@
+use anyhow::{bail, Context, Result};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rust_decimal::prelude::*;
+use std::collections::VecDeque;
+
+/// How many recent rows a duplicate may repeat.
+const RECENT_ROWS: usize = 1000;
+
+/// Options controlling the synthetic transaction data written by a Generator. Rates are the
+/// probability of each row being of that kind, and must add up to at most one. Rows that are not
+/// malformed, duplicated or part of a dispute are deposits and withdrawals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorOptions {
+    /// Number of distinct clients, numbered from 1.
+    pub clients: u64,
+    /// Number of rows to write, not counting the header.
+    pub rows: u64,
+    /// Probability of a row disputing one of the client's earlier deposits.
+    pub dispute_rate: f64,
+    /// Probability of a row resolving one of the client's open disputes.
+    pub resolve_rate: f64,
+    /// Probability of a row charging back one of the client's open disputes.
+    pub chargeback_rate: f64,
+    /// Probability of a row that cannot be parsed into a transaction.
+    pub malformed_rate: f64,
+    /// Probability of a row repeating a recent deposit or withdrawal exactly.
+    pub duplicate_rate: f64,
+    /// Seed of the random number generator. The same options always produce the same data.
+    pub seed: u64,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        GeneratorOptions {
+            clients: 100,
+            rows: 10_000,
+            dispute_rate: 0.02,
+            resolve_rate: 0.01,
+            chargeback_rate: 0.005,
+            malformed_rate: 0.0,
+            duplicate_rate: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+/// What the Generator knows about a client's account, so that disputes reference real deposits
+/// and withdrawals rarely overdraw.
+#[derive(Debug, Clone)]
+struct ClientState {
+    /// Available funds in cents, assuming every generated transaction was applied.
+    available: i64,
+    /// Deposits that have not been disputed yet, as transaction identifier and cents.
+    deposits: Vec<(u64, i64)>,
+    /// Deposits under dispute, as transaction identifier and cents.
+    disputes: Vec<(u64, i64)>,
+}
+
+/// Writes realistic synthetic transaction CSV for load testing and demos, in the
+/// `type,client,tx,amount` layout read by `Input::from_csv_data`.
+#[derive(Debug)]
+pub struct Generator {
+    options: GeneratorOptions,
+    rng: ChaCha8Rng,
+    clients: Vec<ClientState>,
+    recent: VecDeque<Vec<String>>,
+    next_tx: u64,
+}
+
+impl Generator {
+    /// Creates a Generator, checking that the options describe valid data.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Controls the size and mix of the data.
+    pub fn new(options: GeneratorOptions) -> Result<Generator> {
+        let rates = [
+            options.dispute_rate,
+            options.resolve_rate,
+            options.chargeback_rate,
+            options.malformed_rate,
+            options.duplicate_rate,
+        ];
+        if rates.iter().any(|rate| !(0.0..=1.0).contains(rate)) {
+            bail!("Rates must be between 0 and 1.");
+        }
+        if rates.iter().sum::<f64>() > 1.0 {
+            bail!("Rates must add up to at most 1.");
+        }
+        if options.clients == 0 {
+            bail!("At least one client is required.");
+        }
+        let clients = vec![
+            ClientState {
+                available: 0,
+                deposits: Vec::new(),
+                disputes: Vec::new(),
+            };
+            options.clients as usize
+        ];
+        Ok(Generator {
+            rng: ChaCha8Rng::seed_from_u64(options.seed),
+            options,
+            clients,
+            recent: VecDeque::new(),
+            next_tx: 1,
+        })
+    }
+
+    /// Writes the header and every row as CSV into a provided target that implements the `Write`
+    /// trait.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Anything that implements the Write trait.
+    pub fn write_csv(mut self, writer: impl std::io::Write) -> Result<()> {
+        let mut csv_writer = csv::WriterBuilder::new().flexible(true).from_writer(writer);
+        csv_writer
+            .write_record(["type", "client", "tx", "amount"])
+            .context("Failed to write generated header.")?;
+        for _ in 0..self.options.rows {
+            let row = self.row();
+            csv_writer
+                .write_record(&row)
+                .context("Failed to write generated row.")?;
+        }
+        csv_writer
+            .flush()
+            .context("Writer failed to write generated data.")
+    }
+
+    /// Generates the next row, choosing its kind according to the rates.
+    fn row(&mut self) -> Vec<String> {
+        let index = self.rng.gen_range(0..self.clients.len());
+        let client = (index + 1).to_string();
+        let mut roll: f64 = self.rng.gen();
+
+        roll -= self.options.malformed_rate;
+        if roll < 0.0 {
+            return self.malformed(client);
+        }
+        roll -= self.options.duplicate_rate;
+        if roll < 0.0 && !self.recent.is_empty() {
+            let recent = self.rng.gen_range(0..self.recent.len());
+            return self.recent[recent].clone();
+        }
+        let state = &mut self.clients[index];
+        roll -= self.options.dispute_rate;
+        if roll < 0.0 && !state.deposits.is_empty() {
+            let deposit = state
+                .deposits
+                .swap_remove(self.rng.gen_range(0..state.deposits.len()));
+            state.available -= deposit.1;
+            state.disputes.push(deposit);
+            return vec![
+                String::from("dispute"),
+                client,
+                deposit.0.to_string(),
+                String::new(),
+            ];
+        }
+        roll -= self.options.resolve_rate;
+        if roll < 0.0 && !state.disputes.is_empty() {
+            let dispute = state
+                .disputes
+                .swap_remove(self.rng.gen_range(0..state.disputes.len()));
+            state.available += dispute.1;
+            return vec![
+                String::from("resolve"),
+                client,
+                dispute.0.to_string(),
+                String::new(),
+            ];
+        }
+        roll -= self.options.chargeback_rate;
+        if roll < 0.0 && !state.disputes.is_empty() {
+            let dispute = state
+                .disputes
+                .swap_remove(self.rng.gen_range(0..state.disputes.len()));
+            return vec![
+                String::from("chargeback"),
+                client,
+                dispute.0.to_string(),
+                String::new(),
+            ];
+        }
+
+        let tx = self.next_tx;
+        self.next_tx += 1;
+        // Most clients withdraw less than they hold, but some overdraw and are rejected
+        let row = if state.available > 0 && self.rng.gen_bool(0.4) {
+            let cents = self
+                .rng
+                .gen_range(1..=state.available + state.available / 10);
+            if cents <= state.available {
+                state.available -= cents;
+            }
+            vec![
+                String::from("withdraw"),
+                client,
+                tx.to_string(),
+                amount(cents),
+            ]
+        } else {
+            let cents = self.rng.gen_range(100..=100_000);
+            state.available += cents;
+            state.deposits.push((tx, cents));
+            vec![
+                String::from("deposit"),
+                client,
+                tx.to_string(),
+                amount(cents),
+            ]
+        };
+        if self.recent.len() == RECENT_ROWS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(row.clone());
+        row
+    }
+
+    /// Generates a row that cannot be parsed into a transaction.
+    fn malformed(&mut self, client: String) -> Vec<String> {
+        let tx = self.next_tx.to_string();
+        match self.rng.gen_range(0..5) {
+            0 => vec![String::from("refund"), client, tx, amount(100)],
+            1 => vec![String::from("deposit"), client, tx, String::new()],
+            2 => vec![String::from("withdraw"), client, tx, String::from("1,0")],
+            3 => vec![String::from("deposit"), String::new(), tx, amount(100)],
+            _ => vec![String::from("deposit"), client, tx],
+        }
+    }
+}
+
+/// Formats an amount in cents with two decimal places.
+fn amount(cents: i64) -> String {
+    Decimal::new(cents, 2).to_string()
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{Input, InputOptions};
+
+    fn generate(options: GeneratorOptions) -> Vec<u8> {
+        let mut data = Vec::new();
+        Generator::new(options)
+            .unwrap()
+            .write_csv(&mut data)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_generator() {
+        let options = GeneratorOptions {
+            clients: 5,
+            rows: 500,
+            dispute_rate: 0.1,
+            resolve_rate: 0.05,
+            chargeback_rate: 0.02,
+            malformed_rate: 0.1,
+            duplicate_rate: 0.05,
+            seed: 7,
+        };
+        let data = generate(options.clone());
+        assert_eq!(data, generate(options.clone()));
+        assert_ne!(
+            data,
+            generate(GeneratorOptions {
+                seed: 8,
+                ..options.clone()
+            })
+        );
+
+        let text = String::from_utf8(data.clone()).unwrap();
+        assert!(text.starts_with("type,client,tx,amount\n"));
+        assert_eq!(text.lines().count(), 501);
+        for kind in ["deposit", "withdraw", "dispute", "resolve", "chargeback"] {
+            assert!(text.lines().any(|line| line.starts_with(kind)));
+        }
+
+        // Only the malformed rows are skipped when reading the data back
+        let input = Input::from_csv_data(&data, &InputOptions::default()).unwrap();
+        let clean = generate(GeneratorOptions {
+            malformed_rate: 0.0,
+            ..options
+        });
+        let clean = Input::from_csv_data(&clean, &InputOptions::default()).unwrap();
+        assert!(input.transactions.len() < 500);
+        assert_eq!(clean.transactions.len(), 500);
+    }
+
+    #[test]
+    fn test_generator_options() {
+        assert!(Generator::new(GeneratorOptions {
+            dispute_rate: 0.6,
+            malformed_rate: 0.6,
+            ..GeneratorOptions::default()
+        })
+        .is_err());
+        assert!(Generator::new(GeneratorOptions {
+            clients: 0,
+            ..GeneratorOptions::default()
+        })
+        .is_err());
+    }
+}
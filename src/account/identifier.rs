@@ -0,0 +1,135 @@
+use serde::de::{self, Unexpected, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// Identifier of a client or a transaction. Identifiers written as a plain 64-bit number, with no
+/// leading zeros, are held as numbers. Any other identifier, such as a UUID, `007` or a run of
+/// digits too large for 64 bits, is held as text exactly as it appears in the source data, so
+/// every identifier is written out unchanged. Deserialized strings follow the same rules as
+/// `FromStr`, so `"7"` in JSON or YAML names the same client as `7` in CSV.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(untagged)]
+pub enum Identifier {
+    /// An identifier written as a 64-bit number without leading zeros.
+    Numeric(u64),
+    /// Any other identifier.
+    Text(String),
+}
+
+impl FromStr for Identifier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.trim();
+        if value.is_empty() {
+            return Err(String::from("Identifiers cannot be empty."));
+        }
+        let canonical =
+            value.chars().all(|c| c.is_ascii_digit()) && (value == "0" || !value.starts_with('0'));
+        match value.parse::<u64>() {
+            Ok(number) if canonical => Ok(Identifier::Numeric(number)),
+            _ => Ok(Identifier::Text(String::from(value))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Identifier {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(IdentifierVisitor)
+    }
+}
+
+/// Reads an Identifier from a number or a string, parsing strings with `FromStr`.
+struct IdentifierVisitor;
+
+impl<'de> Visitor<'de> for IdentifierVisitor {
+    type Value = Identifier;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a non-negative integer or a non-empty string")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Identifier, E> {
+        Ok(Identifier::Numeric(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Identifier, E> {
+        u64::try_from(value)
+            .map(Identifier::Numeric)
+            .map_err(|_| E::invalid_value(Unexpected::Signed(value), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Identifier, E> {
+        value.parse().map_err(E::custom)
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Identifier::Numeric(value) => write!(f, "{}", value),
+            Identifier::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<u64> for Identifier {
+    fn from(value: u64) -> Self {
+        Identifier::Numeric(value)
+    }
+}
+
+impl From<&str> for Identifier {
+    fn from(value: &str) -> Self {
+        Identifier::Text(String::from(value))
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_follows_from_str() {
+        let parsed: Vec<Identifier> =
+            serde_json::from_str(r#"[7, "7", " 7 ", "3f2b8c1e", "18446744073709551615", "0"]"#)
+                .unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                Identifier::Numeric(7),
+                Identifier::Numeric(7),
+                Identifier::Numeric(7),
+                Identifier::Text(String::from("3f2b8c1e")),
+                Identifier::Numeric(u64::MAX),
+                Identifier::Numeric(0),
+            ]
+        );
+        let keys: std::collections::BTreeMap<Identifier, u32> =
+            serde_yaml::from_str("8: 1\n'008': 2\n").unwrap();
+        assert_eq!(
+            keys.keys().cloned().collect::<Vec<_>>(),
+            vec![
+                Identifier::Numeric(8),
+                Identifier::Text(String::from("008"))
+            ]
+        );
+
+        assert!(serde_json::from_str::<Identifier>(r#""""#).is_err());
+        assert!(serde_json::from_str::<Identifier>("-1").is_err());
+    }
+
+    #[test]
+    fn test_non_canonical_digits_round_trip() {
+        for value in ["007", "00", "18446744073709551616"] {
+            let identifier: Identifier = value.parse().unwrap();
+            assert_eq!(identifier, Identifier::Text(String::from(value)));
+            assert_eq!(identifier.to_string(), value);
+        }
+        assert_ne!("007".parse::<Identifier>(), "7".parse::<Identifier>());
+    }
+}
@@ -0,0 +1,162 @@
+use super::{AsOf, OpeningBalance, ReorderBuffer, Transaction, TransactionSet};
+use anyhow::{Context, Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+
+/// Options controlling how transactions are read from source data.
+#[derive(Debug, Clone, Default)]
+pub struct InputOptions {
+    /// The last point in the stream to include. All rows are read if `None`.
+    pub as_of: Option<AsOf>,
+    /// When present, transactions are reordered by timestamp, tolerating arrivals up to this far
+    /// behind the latest timestamp seen. Rows are applied in file order if `None`.
+    pub lateness: Option<Duration>,
+}
+
+/// Transactions read from source data, in the order they should be applied.
+#[derive(Debug, Clone)]
+pub struct Input {
+    /// Header row of the source data.
+    pub headers: csv::StringRecord,
+    /// Transactions in application order.
+    pub transactions: Vec<Transaction>,
+    /// Rows that arrived too late to be reordered, exactly as they appeared in the source data.
+    pub late: Vec<csv::StringRecord>,
+}
+
+impl Input {
+    /// Reads transactions from CSV data. Rows that cannot be parsed are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Reference to a Vec<u8> of CSV data.
+    /// * `options` - Controls the stopping point and ordering of the transactions.
+    pub fn from_csv_data(data: &[u8], options: &InputOptions) -> Result<Input, Error> {
+        let mut csv_results = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(data);
+        let headers = csv_results.headers()?.clone();
+        let mut input = Input {
+            headers: headers.clone(),
+            transactions: Vec::new(),
+            late: Vec::new(),
+        };
+        let mut buffer = options.lateness.map(ReorderBuffer::new);
+        for result in csv_results.records() {
+            let record = match result {
+                Ok(record) => record,
+                Err(_) => continue,
+            };
+            let line = record.position().map_or(0, |position| position.line());
+            if options
+                .as_of
+                .as_ref()
+                .is_some_and(|point| point.excludes_line(line))
+            {
+                break;
+            }
+            let transaction = match Transaction::from_record(&headers, &record) {
+                Ok(transaction) => transaction,
+                Err(_) => continue,
+            };
+            let complete = match &mut buffer {
+                Some(buffer) => {
+                    if buffer.push(transaction).is_err() {
+                        warn!("Line {} arrived outside the lateness window.", line);
+                        input.late.push(record);
+                    }
+                    let mut complete = false;
+                    while let Some(transaction) = buffer.pop_ready() {
+                        complete = input.accept(transaction, options);
+                        if complete {
+                            break;
+                        }
+                    }
+                    complete
+                }
+                None => input.accept(transaction, options),
+            };
+            if complete {
+                return Ok(input);
+            }
+        }
+        if let Some(buffer) = buffer {
+            for transaction in buffer.drain() {
+                if input.accept(transaction, options) {
+                    break;
+                }
+            }
+        }
+        Ok(input)
+    }
+
+    /// Adds a transaction in application order, returning whether the stopping point has been
+    /// reached.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    /// * `options` - Provides the stopping point, if any.
+    fn accept(&mut self, transaction: Transaction, options: &InputOptions) -> bool {
+        match &options.as_of {
+            Some(point) if point.excludes(&transaction) => false,
+            Some(point) => {
+                let last = point.ends_with(&transaction);
+                self.transactions.push(transaction);
+                last
+            }
+            None => {
+                self.transactions.push(transaction);
+                false
+            }
+        }
+    }
+
+    /// Places the transactions that seed accounts with their opening balances before every other
+    /// transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `balances` - The state of each account before the transactions.
+    pub fn with_opening_balances(mut self, balances: &[OpeningBalance]) -> Input {
+        let mut transactions: Vec<Transaction> =
+            balances.iter().map(OpeningBalance::transaction).collect();
+        transactions.append(&mut self.transactions);
+        self.transactions = transactions;
+        self
+    }
+
+    /// The latest timestamp carried by any of the transactions, if any carry one.
+    pub fn latest_timestamp(&self) -> Option<DateTime<Utc>> {
+        self.transactions
+            .iter()
+            .filter_map(|transaction| transaction.timestamp)
+            .max()
+    }
+
+    /// Groups the transactions into one TransactionSet per client, preserving application order.
+    pub fn transaction_sets(self) -> Vec<TransactionSet> {
+        TransactionSet::from_transactions(self.transactions)
+    }
+
+    /// Writes the rows that arrived too late, with the original header, as CSV into a provided
+    /// target that implements the `Write` trait. The result can be fed back in as input.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Anything that implements the Write trait.
+    pub fn write_late(&self, writer: impl std::io::Write) -> Result<()> {
+        let mut csv_writer = csv::Writer::from_writer(writer);
+        csv_writer
+            .write_record(&self.headers)
+            .context("Failed to write quarantine header.")?;
+        for record in self.late.iter() {
+            csv_writer
+                .write_record(record)
+                .context("Failed to write quarantined row.")?;
+        }
+        csv_writer
+            .flush()
+            .context("Writer failed to write quarantined rows.")
+    }
+}
@@ -0,0 +1,96 @@
+use super::{Balance, Currency, Identifier, Transaction};
+use rust_decimal::prelude::*;
+use std::fmt;
+
+/// A transaction that left an account balance inconsistent, with the balance directly before and
+/// after it.
+#[derive(Debug, Clone)]
+pub struct InvariantViolation {
+    /// Client identifier of the account.
+    pub client: Identifier,
+    /// Currency of the account, if known.
+    pub currency: Option<Currency>,
+    /// The transaction that broke the invariant.
+    pub transaction: Transaction,
+    /// The balance before the transaction was applied.
+    pub before: Balance,
+    /// The balance after the transaction was applied.
+    pub after: Balance,
+    /// The invariant that no longer holds.
+    pub reason: &'static str,
+}
+
+impl Balance {
+    /// Describes the first balance invariant that does not hold, if any. The invariants are that
+    /// `available + held == total`, that `held` is not negative, and that `authorized` is a part
+    /// of `held`.
+    pub fn invariant_violation(&self) -> Option<&'static str> {
+        if self.available + self.held != self.total {
+            Some("available + held does not equal total")
+        } else if self.held < Decimal::zero() {
+            Some("held is negative")
+        } else if self.authorized < Decimal::zero() || self.authorized > self.held {
+            Some("authorized is not within held")
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tx {} ({}) on client {} broke a balance invariant: {}. \
+             Before: available {}, held {}, total {}. After: available {}, held {}, total {}.",
+            self.transaction.tx,
+            self.transaction.transaction_type.name(),
+            self.client,
+            self.reason,
+            self.before.available,
+            self.before.held,
+            self.before.total,
+            self.after.available,
+            self.after.held,
+            self.after.total
+        )
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balance(available: i64, held: i64, authorized: i64, total: i64) -> Balance {
+        Balance {
+            available: Decimal::new(available, 0),
+            held: Decimal::new(held, 0),
+            authorized: Decimal::new(authorized, 0),
+            total: Decimal::new(total, 0),
+            locked: false,
+            closed: false,
+            negative: false,
+        }
+    }
+
+    #[test]
+    fn test_invariant_violation() {
+        assert_eq!(balance(-5, 10, 4, 5).invariant_violation(), None);
+        assert_eq!(
+            balance(5, 5, 0, 9).invariant_violation(),
+            Some("available + held does not equal total")
+        );
+        assert_eq!(
+            balance(10, -5, 0, 5).invariant_violation(),
+            Some("held is negative")
+        );
+        assert_eq!(
+            balance(0, 5, 6, 5).invariant_violation(),
+            Some("authorized is not within held")
+        );
+    }
+}
@@ -0,0 +1,818 @@
+use super::{
+    Account, Balance, Currency, Identifier, InvariantViolation, Policy, RejectedTransaction,
+    Rejection, RiskAction, RiskEvent, RiskRule, Step, Transaction, TransactionType,
+};
+use chrono::{DateTime, Utc};
+use log::{debug, error};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// The accounts of every client, built by applying a stream of transactions in order. Each client
+/// has one account per currency. Transfers and disputes of transfers involve two clients and are
+/// applied to both accounts or to neither.
+#[derive(Debug)]
+pub struct Ledger {
+    /// Accounts by client identifier and currency.
+    accounts: BTreeMap<(Identifier, Option<Currency>), Account>,
+    /// Source and destination clients of every applied transfer, by transaction identifier.
+    transfers: HashMap<Identifier, (Identifier, Identifier)>,
+    /// Currency of the account every applied transaction belongs to, by transaction identifier.
+    currencies: HashMap<Identifier, Option<Currency>>,
+    /// The rules every account applies to its transactions.
+    policy: Arc<Policy>,
+    /// Risk rules evaluated on every applied transaction, and what to do when they fire.
+    rules: Vec<(Box<dyn RiskRule>, RiskAction)>,
+    /// Every firing of a risk rule, in order.
+    events: Vec<RiskEvent>,
+    /// Every rejected transaction, in order.
+    rejections: Vec<RejectedTransaction>,
+    /// Whether balance invariants are checked after every applied transaction.
+    paranoid: bool,
+    /// Every transaction that broke a balance invariant, in order.
+    violations: Vec<InvariantViolation>,
+}
+
+impl Ledger {
+    /// Creates a Ledger without any accounts. The risk rules configured in the Policy are
+    /// evaluated on every applied transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The rules every account applies to its transactions.
+    pub fn new(policy: Arc<Policy>) -> Ledger {
+        let rules = policy
+            .risk
+            .rules
+            .iter()
+            .map(|config| (config.rule.rule(), config.action))
+            .collect();
+        Ledger {
+            accounts: BTreeMap::new(),
+            transfers: HashMap::new(),
+            currencies: HashMap::new(),
+            policy,
+            rules,
+            events: Vec::new(),
+            rejections: Vec::new(),
+            paranoid: cfg!(debug_assertions),
+            violations: Vec::new(),
+        }
+    }
+
+    /// Enables checking balance invariants after every applied transaction. Checks always run in
+    /// debug builds.
+    ///
+    /// # Arguments
+    ///
+    /// * `paranoid` - Whether to check invariants in release builds.
+    pub fn paranoid(mut self, paranoid: bool) -> Ledger {
+        self.paranoid |= paranoid;
+        self
+    }
+
+    /// Every transaction that broke a balance invariant so far, in order. Always empty unless
+    /// invariant checks are enabled.
+    pub fn violations(&self) -> &[InvariantViolation] {
+        &self.violations
+    }
+
+    /// Adds a risk rule to evaluate on every applied transaction, after those from the Policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `rule` - The rule to evaluate.
+    /// * `action` - What happens to the account when the rule fires.
+    pub fn with_rule(mut self, rule: Box<dyn RiskRule>, action: RiskAction) -> Ledger {
+        self.rules.push((rule, action));
+        self
+    }
+
+    /// Every firing of a risk rule so far, in order.
+    pub fn risk_events(&self) -> &[RiskEvent] {
+        &self.events
+    }
+
+    /// Every transaction rejected so far, in order. A rejected transfer is recorded once.
+    pub fn rejections(&self) -> &[RejectedTransaction] {
+        &self.rejections
+    }
+
+    /// The account of a client in a currency, if any transaction has named it.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client identifier
+    /// * `currency` - Currency of the account, or `None` for transactions without one.
+    pub fn account(&self, client: &Identifier, currency: Option<&Currency>) -> Option<&Account> {
+        self.accounts.get(&(client.clone(), currency.cloned()))
+    }
+
+    /// Consumes the Ledger, returning every account in client and currency order.
+    pub fn into_accounts(self) -> impl Iterator<Item = Account> {
+        self.accounts.into_values()
+    }
+
+    /// Applies the next transaction of the stream. Returns a Step for every account involved,
+    /// preceded by a Step for every dispute on those accounts that expired before the transaction
+    /// occurred.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    pub fn apply(&mut self, transaction: Transaction) -> Vec<Step> {
+        let client = transaction.client.clone();
+        let currency = self.currency(&transaction);
+        let counterparty = self.counterparty(&transaction);
+        let mut steps = Vec::new();
+        let mut clients = vec![client.clone()];
+        clients.extend(counterparty.clone().filter(|other| *other != client));
+        for client in clients.iter() {
+            let key = (client.clone(), currency.clone());
+            let policy = &self.policy;
+            self.accounts.entry(key.clone()).or_insert_with(|| {
+                Account::with_policy(client.clone(), policy.clone()).in_currency(currency.clone())
+            });
+            if let Some(timestamp) = transaction.timestamp {
+                steps.extend(self.expire(&key, timestamp));
+            }
+        }
+        let before: Vec<Balance> = clients
+            .iter()
+            .map(|client| self.accounts[&(client.clone(), currency.clone())].balance())
+            .collect();
+        let reused = Ledger::registers(&transaction)
+            && self
+                .currencies
+                .get(&transaction.tx)
+                .is_some_and(|used| *used != currency);
+        let outcome = match counterparty {
+            _ if reused => Err(Rejection::DuplicateTransaction),
+            Some(other) if other == client => Err(Rejection::SelfTransfer),
+            Some(other) => {
+                let first_key = (client.clone(), currency.clone());
+                let second_key = (other.clone(), currency.clone());
+                let mut first = self.accounts.remove(&first_key).unwrap();
+                let mut second = self.accounts.remove(&second_key).unwrap();
+                let outcome = Account::resolve_paired_transaction(
+                    &mut first,
+                    &mut second,
+                    transaction.clone(),
+                );
+                self.accounts.insert(first_key, first);
+                self.accounts.insert(second_key, second);
+                if outcome.is_ok() {
+                    if let TransactionType::Transfer { .. } = transaction.transaction_type {
+                        self.transfers
+                            .insert(transaction.tx.clone(), (client.clone(), other));
+                    }
+                }
+                outcome
+            }
+            None => self
+                .accounts
+                .get_mut(&(client.clone(), currency.clone()))
+                .unwrap()
+                .resolve_new_transaction(transaction.clone()),
+        };
+        if let Err(rejection) = &outcome {
+            debug!(
+                "Client {} rejected tx {}: {}",
+                client, transaction.tx, rejection
+            );
+            self.rejections.push(RejectedTransaction::new(
+                &transaction,
+                currency.clone(),
+                rejection,
+            ));
+        } else {
+            if Ledger::registers(&transaction) {
+                self.currencies
+                    .entry(transaction.tx.clone())
+                    .or_insert_with(|| currency.clone());
+            }
+            if let TransactionType::Opening {
+                held_tx: Some(tx), ..
+            } = &transaction.transaction_type
+            {
+                self.currencies.insert(tx.clone(), currency.clone());
+            }
+            for (client, before) in clients.iter().zip(before) {
+                let key = (client.clone(), currency.clone());
+                self.check(&key, &transaction, before);
+                self.evaluate(&key, &transaction);
+            }
+        }
+        for client in clients {
+            let balance = self.accounts[&(client.clone(), currency.clone())].balance();
+            steps.push(Step {
+                client,
+                currency: currency.clone(),
+                transaction: transaction.clone(),
+                outcome: outcome.clone(),
+                balance,
+                automatic: false,
+            });
+        }
+        steps
+    }
+
+    /// Closes every dispute that has expired by the provided time on every account. Returns a
+    /// Step for each closure.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    pub fn advance_to(&mut self, now: DateTime<Utc>) -> Vec<Step> {
+        let keys: Vec<(Identifier, Option<Currency>)> = self.accounts.keys().cloned().collect();
+        keys.iter().flat_map(|key| self.expire(key, now)).collect()
+    }
+
+    /// Closes every dispute on an account that has expired by the provided time. An expired
+    /// dispute of a transfer is closed on both accounts involved, like a resolve or chargeback of
+    /// the transfer, and yields a Step for each of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Client identifier and currency of the account.
+    /// * `now` - The current time.
+    fn expire(&mut self, key: &(Identifier, Option<Currency>), now: DateTime<Utc>) -> Vec<Step> {
+        let mut steps = Vec::new();
+        while let Some(expired) = self
+            .accounts
+            .get(key)
+            .and_then(|account| account.next_expiry(now))
+        {
+            let mut keys = vec![key.clone()];
+            keys.extend(
+                self.counterparty(&expired)
+                    .map(|other| (other, key.1.clone()))
+                    .filter(|other| other != key && self.accounts.contains_key(other)),
+            );
+            let before: Vec<Balance> = keys
+                .iter()
+                .map(|key| self.accounts[key].balance())
+                .collect();
+            let outcome = match keys.as_slice() {
+                [first_key, second_key] => {
+                    let mut first = self.accounts.remove(first_key).unwrap();
+                    let mut second = self.accounts.remove(second_key).unwrap();
+                    let outcome =
+                        Account::close_expired_pair(&mut first, &mut second, expired.clone());
+                    self.accounts.insert(first_key.clone(), first);
+                    self.accounts.insert(second_key.clone(), second);
+                    outcome
+                }
+                _ => self
+                    .accounts
+                    .get_mut(key)
+                    .unwrap()
+                    .close_expired(expired.clone()),
+            };
+            if let Err(rejection) = &outcome {
+                debug!(
+                    "Client {} rejected expiry of tx {}: {}",
+                    key.0, expired.tx, rejection
+                );
+                self.rejections
+                    .push(RejectedTransaction::new(&expired, key.1.clone(), rejection));
+            }
+            for (key, before) in keys.iter().zip(before) {
+                if outcome.is_ok() {
+                    self.check(key, &expired, before);
+                }
+                steps.push(Step {
+                    client: key.0.clone(),
+                    currency: key.1.clone(),
+                    transaction: expired.clone(),
+                    outcome: outcome.clone(),
+                    balance: self.accounts[key].balance(),
+                    automatic: true,
+                });
+            }
+        }
+        steps
+    }
+
+    /// Checks the balance invariants of an account after a transaction was applied to it,
+    /// recording a violation if they no longer hold. Does nothing unless checks are enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Client identifier and currency of the account.
+    /// * `transaction` - The applied transaction.
+    /// * `before` - The balance before the transaction was applied.
+    fn check(
+        &mut self,
+        key: &(Identifier, Option<Currency>),
+        transaction: &Transaction,
+        before: Balance,
+    ) {
+        if !self.paranoid {
+            return;
+        }
+        let after = match self.accounts.get(key) {
+            Some(account) => account.balance(),
+            None => return,
+        };
+        if let Some(reason) = after.invariant_violation() {
+            let violation = InvariantViolation {
+                client: key.0.clone(),
+                currency: key.1.clone(),
+                transaction: transaction.clone(),
+                before,
+                after,
+                reason,
+            };
+            error!("{}", violation);
+            self.violations.push(violation);
+        }
+    }
+
+    /// Evaluates every risk rule against a transaction just applied to an account, flagging or
+    /// locking the account and recording an event for each rule that fires.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Client identifier and currency of the account.
+    /// * `transaction` - The applied transaction.
+    fn evaluate(&mut self, key: &(Identifier, Option<Currency>), transaction: &Transaction) {
+        let account = match self.accounts.get_mut(key) {
+            Some(account) => account,
+            None => return,
+        };
+        for (rule, action) in self.rules.iter() {
+            if !rule.fires(account, transaction) {
+                continue;
+            }
+            debug!(
+                "Risk rule {} fired on client {} for tx {}",
+                rule.name(),
+                key.0,
+                transaction.tx
+            );
+            account.flag(rule.name());
+            if *action == RiskAction::Lock {
+                account.lock();
+            }
+            self.events.push(RiskEvent {
+                client: key.0.clone(),
+                currency: key.1.clone(),
+                tx: transaction.tx.clone(),
+                timestamp: transaction.timestamp,
+                rule: String::from(rule.name()),
+                action: *action,
+            });
+        }
+    }
+
+    /// The currency of the account a transaction applies to. Transactions that act on an earlier
+    /// transaction follow it to its account, and any other transaction goes to the account in its
+    /// own currency.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn currency(&self, transaction: &Transaction) -> Option<Currency> {
+        match Ledger::references(transaction).and_then(|tx| self.currencies.get(&tx)) {
+            Some(currency) => currency.clone(),
+            None => transaction.currency.clone(),
+        }
+    }
+
+    /// The identifier of the earlier transaction a dispute, resolve, chargeback, capture or void
+    /// acts on.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn references(transaction: &Transaction) -> Option<Identifier> {
+        match transaction.transaction_type {
+            TransactionType::Dispute(_)
+            | TransactionType::Resolve(_)
+            | TransactionType::Chargeback(_)
+            | TransactionType::Capture(_)
+            | TransactionType::Void => Some(transaction.tx.clone()),
+            _ => None,
+        }
+    }
+
+    /// Indicates whether a transaction claims its identifier for the account it is applied to.
+    /// Opening balances all share one identifier, which each account holds separately.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn registers(transaction: &Transaction) -> bool {
+        Ledger::references(transaction).is_none()
+            && !matches!(
+                transaction.transaction_type,
+                TransactionType::Opening { .. }
+            )
+    }
+
+    /// The other client involved in a transfer, or in a dispute, resolve or chargeback of one.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn counterparty(&self, transaction: &Transaction) -> Option<Identifier> {
+        match &transaction.transaction_type {
+            TransactionType::Transfer { destination, .. } => Some(destination.clone()),
+            TransactionType::Dispute(_)
+            | TransactionType::Resolve(_)
+            | TransactionType::Chargeback(_) => match self.transfers.get(&transaction.tx) {
+                Some((source, destination)) if *source == transaction.client => {
+                    Some(destination.clone())
+                }
+                Some((source, destination)) if *destination == transaction.client => {
+                    Some(source.clone())
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::*;
+
+    fn transaction(transaction_type: TransactionType, tx: u64, client: u64) -> Transaction {
+        Transaction {
+            transaction_type,
+            tx: tx.into(),
+            client: client.into(),
+            timestamp: None,
+            currency: None,
+        }
+    }
+
+    fn transfer(tx: u64, source: u64, destination: u64, amount: i64) -> Transaction {
+        transaction(
+            TransactionType::Transfer {
+                destination: destination.into(),
+                amount: Decimal::new(amount, 0),
+            },
+            tx,
+            source,
+        )
+    }
+
+    fn funded_ledger() -> Ledger {
+        let mut ledger = Ledger::new(Arc::default());
+        ledger.apply(transaction(
+            TransactionType::Deposit(Decimal::new(10, 0)),
+            1,
+            1,
+        ));
+        ledger
+    }
+
+    #[test]
+    fn test_transfer_applies_both_legs() {
+        let mut ledger = funded_ledger();
+        let steps = ledger.apply(transfer(2, 1, 2, 4));
+
+        assert_eq!(steps.len(), 2);
+        assert!(steps.iter().all(|step| step.outcome.is_ok()));
+        assert_eq!(
+            ledger.account(&1.into(), None).unwrap().balance().available,
+            Decimal::new(6, 0)
+        );
+        assert_eq!(
+            ledger.account(&2.into(), None).unwrap().balance().available,
+            Decimal::new(4, 0)
+        );
+    }
+
+    #[test]
+    fn test_rejected_transfer_applies_neither_leg() {
+        let mut ledger = funded_ledger();
+        let steps = ledger.apply(transfer(2, 1, 2, 20));
+        assert!(steps
+            .iter()
+            .all(|step| step.outcome == Err(Rejection::InsufficientFunds)));
+
+        // Lock client 3 with a chargeback, then try to pay it.
+        ledger.apply(transaction(
+            TransactionType::Deposit(Decimal::new(1, 0)),
+            3,
+            3,
+        ));
+        ledger.apply(transaction(TransactionType::Dispute(None), 3, 3));
+        ledger.apply(transaction(TransactionType::Chargeback(None), 3, 3));
+        let steps = ledger.apply(transfer(4, 1, 3, 4));
+        assert!(steps
+            .iter()
+            .all(|step| step.outcome == Err(Rejection::AccountLocked)));
+
+        assert_eq!(
+            ledger.apply(transfer(5, 1, 1, 4))[0].outcome,
+            Err(Rejection::SelfTransfer)
+        );
+        let reasons: Vec<&str> = ledger
+            .rejections()
+            .iter()
+            .map(|rejected| rejected.reason.as_str())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "insufficient available funds",
+                "account is locked",
+                "transfer source and destination are the same client"
+            ]
+        );
+        assert_eq!(
+            ledger.account(&1.into(), None).unwrap().balance().available,
+            Decimal::new(10, 0)
+        );
+        assert_eq!(
+            ledger.account(&2.into(), None).unwrap().balance().available,
+            Decimal::new(00, 1)
+        );
+        assert_eq!(
+            ledger
+                .account(&1.into(), None)
+                .unwrap()
+                .transactions()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_transfer_disputed_as_pair() {
+        let mut ledger = funded_ledger();
+        ledger.apply(transfer(2, 1, 2, 4));
+        let steps = ledger.apply(transaction(TransactionType::Dispute(None), 2, 2));
+        assert!(steps.iter().all(|step| step.outcome.is_ok()));
+
+        let destination = ledger.account(&2.into(), None).unwrap().balance();
+        assert_eq!(destination.available, Decimal::new(0, 0));
+        assert_eq!(destination.held, Decimal::new(4, 0));
+        let source = ledger.account(&1.into(), None).unwrap().balance();
+        assert_eq!(source.available, Decimal::new(6, 0));
+        assert_eq!(source.held, Decimal::new(4, 0));
+
+        ledger.apply(transaction(TransactionType::Chargeback(None), 2, 1));
+        let destination = ledger.account(&2.into(), None).unwrap().balance();
+        assert_eq!(destination.total, Decimal::new(0, 0));
+        assert!(destination.locked);
+        let source = ledger.account(&1.into(), None).unwrap().balance();
+        assert_eq!(source.available, Decimal::new(10, 0));
+        assert_eq!(source.held, Decimal::new(0, 0));
+    }
+
+    #[test]
+    fn test_transfer_dispute_expires_as_pair() {
+        let policy = Policy::from_yaml(
+            b"disputes:\n  expiry:\n    after_days: 30\n    action: chargeback\n",
+        )
+        .unwrap();
+        let mut ledger = Ledger::new(Arc::new(policy));
+        let at = |transaction: Transaction, time: &str| Transaction {
+            timestamp: Transaction::parse_timestamp(time).ok(),
+            ..transaction
+        };
+        ledger.apply(at(
+            transaction(TransactionType::Deposit(Decimal::new(10, 0)), 1, 1),
+            "2021-01-01T00:00:00Z",
+        ));
+        ledger.apply(at(transfer(2, 1, 2, 4), "2021-01-01T00:00:00Z"));
+        ledger.apply(at(
+            transaction(TransactionType::Dispute(None), 2, 2),
+            "2021-01-02T00:00:00Z",
+        ));
+
+        // A later transaction of the destination alone closes the dispute on both accounts.
+        let steps = ledger.apply(at(
+            transaction(TransactionType::Deposit(Decimal::new(1, 0)), 3, 2),
+            "2021-03-01T00:00:00Z",
+        ));
+        let expired: Vec<&Step> = steps.iter().filter(|step| step.automatic).collect();
+        assert_eq!(expired.len(), 2);
+        assert!(expired.iter().all(|step| step.outcome.is_ok()));
+        assert_eq!(
+            expired[0].transaction.transaction_type,
+            TransactionType::Chargeback(None)
+        );
+
+        let destination = ledger.account(&2.into(), None).unwrap().balance();
+        assert_eq!(destination.total, Decimal::new(0, 0));
+        assert_eq!(destination.held, Decimal::new(0, 0));
+        assert!(destination.locked);
+        let source = ledger.account(&1.into(), None).unwrap().balance();
+        assert_eq!(source.available, Decimal::new(10, 0));
+        assert_eq!(source.held, Decimal::new(0, 0));
+        assert!(source.locked);
+        assert!(ledger
+            .account(&1.into(), None)
+            .unwrap()
+            .next_expiry(Transaction::parse_timestamp("2021-03-01T00:00:00Z").unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn test_accounts_per_currency() {
+        let usd: Currency = "usd".parse().unwrap();
+        let eur: Currency = "EUR".parse().unwrap();
+        let in_currency = |transaction_type, tx, currency: &Currency| Transaction {
+            currency: Some(currency.clone()),
+            ..transaction(transaction_type, tx, 1)
+        };
+        let mut ledger = Ledger::new(Arc::default());
+        ledger.apply(in_currency(
+            TransactionType::Deposit(Decimal::new(10, 0)),
+            1,
+            &usd,
+        ));
+        ledger.apply(in_currency(
+            TransactionType::Deposit(Decimal::new(5, 0)),
+            2,
+            &eur,
+        ));
+
+        // Withdrawals only draw on the account in their own currency.
+        assert_eq!(
+            ledger.apply(in_currency(
+                TransactionType::Withdraw(Decimal::new(8, 0)),
+                3,
+                &eur
+            ))[0]
+                .outcome,
+            Err(Rejection::InsufficientFunds)
+        );
+        // A transaction identifier cannot be reused in another currency.
+        assert_eq!(
+            ledger.apply(in_currency(
+                TransactionType::Deposit(Decimal::new(1, 0)),
+                1,
+                &eur
+            ))[0]
+                .outcome,
+            Err(Rejection::DuplicateTransaction)
+        );
+
+        // Disputes follow the disputed transaction to its account.
+        let steps = ledger.apply(transaction(TransactionType::Dispute(None), 1, 1));
+        assert!(steps[0].outcome.is_ok());
+        assert_eq!(steps[0].currency, Some(usd.clone()));
+        assert_eq!(
+            ledger.apply(in_currency(TransactionType::Dispute(None), 2, &usd))[0].outcome,
+            Err(Rejection::CurrencyMismatch)
+        );
+
+        let balance = ledger.account(&1.into(), Some(&usd)).unwrap().balance();
+        assert_eq!(balance.available, Decimal::new(0, 0));
+        assert_eq!(balance.held, Decimal::new(10, 0));
+        let balance = ledger.account(&1.into(), Some(&eur)).unwrap().balance();
+        assert_eq!(balance.available, Decimal::new(5, 0));
+        assert_eq!(balance.held, Decimal::new(00, 1));
+        assert!(ledger.account(&1.into(), None).is_none());
+    }
+
+    #[test]
+    fn test_invariants_hold() {
+        let mut ledger = Ledger::new(Arc::default()).paranoid(true);
+        ledger.apply(transaction(
+            TransactionType::Deposit(Decimal::new(10, 0)),
+            1,
+            1,
+        ));
+        ledger.apply(transfer(2, 1, 2, 4));
+        ledger.apply(transaction(
+            TransactionType::Withdraw(Decimal::new(4, 0)),
+            3,
+            2,
+        ));
+        ledger.apply(transaction(TransactionType::Dispute(None), 2, 2));
+        ledger.apply(transaction(TransactionType::Chargeback(None), 2, 1));
+        ledger.apply(transaction(TransactionType::Dispute(None), 1, 1));
+
+        assert!(ledger.violations().is_empty());
+        assert_eq!(
+            ledger.account(&2.into(), None).unwrap().balance().total,
+            Decimal::new(-4, 0)
+        );
+    }
+
+    #[test]
+    fn test_transfer_requires_ledger() {
+        let mut account = Account::new(1.into());
+        assert_eq!(
+            account.resolve_new_transaction(transfer(1, 1, 2, 4)),
+            Err(Rejection::CounterpartyRequired)
+        );
+    }
+
+    #[test]
+    fn test_risk_rules() {
+        let policy = Policy::from_yaml(
+            b"risk:\n  rules:\n    - rule: repeated_disputes\n      count: 2\n      \
+              window_hours: 24\n      action: lock\n    - rule: deposit_then_withdrawal\n      \
+              within_minutes: 10\n    - rule: stale_dispute\n      older_than_days: 30\n",
+        )
+        .unwrap();
+        let mut ledger = Ledger::new(Arc::new(policy));
+        let at = |transaction_type, tx, client, timestamp: &str| Transaction {
+            timestamp: Transaction::parse_timestamp(timestamp).ok(),
+            ..transaction(transaction_type, tx, client)
+        };
+        let deposit = |amount| TransactionType::Deposit(Decimal::new(amount, 0));
+        ledger.apply(at(deposit(5), 1, 1, "2021-01-01T00:00:00Z"));
+        ledger.apply(at(deposit(5), 2, 1, "2021-01-01T00:01:00Z"));
+        ledger.apply(at(
+            TransactionType::Withdraw(Decimal::new(5, 0)),
+            3,
+            1,
+            "2021-01-01T00:05:00Z",
+        ));
+        assert!(!ledger.account(&1.into(), None).unwrap().balance().locked);
+
+        ledger.apply(at(deposit(5), 4, 1, "2021-02-15T00:00:00Z"));
+        ledger.apply(at(
+            TransactionType::Dispute(None),
+            1,
+            1,
+            "2021-02-15T01:00:00Z",
+        ));
+        ledger.apply(at(
+            TransactionType::Dispute(None),
+            4,
+            1,
+            "2021-02-15T02:00:00Z",
+        ));
+        let account = ledger.account(&1.into(), None).unwrap();
+        assert!(account.balance().locked);
+        assert_eq!(
+            account.flags().iter().collect::<Vec<_>>(),
+            vec![
+                "deposit_then_withdrawal",
+                "repeated_disputes",
+                "stale_dispute"
+            ]
+        );
+
+        let events: Vec<(u64, &str, RiskAction)> = ledger
+            .risk_events()
+            .iter()
+            .map(|event| match event.tx {
+                Identifier::Numeric(tx) => (tx, event.rule.as_str(), event.action),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (3, "deposit_then_withdrawal", RiskAction::Flag),
+                (1, "stale_dispute", RiskAction::Flag),
+                (4, "repeated_disputes", RiskAction::Lock),
+            ]
+        );
+    }
+
+    #[derive(Debug)]
+    struct LargeDeposit;
+
+    impl RiskRule for LargeDeposit {
+        fn name(&self) -> &str {
+            "large_deposit"
+        }
+
+        fn fires(&self, _account: &Account, transaction: &Transaction) -> bool {
+            transaction
+                .transaction_type
+                .amount()
+                .is_some_and(|amount| amount > Decimal::new(100, 0))
+        }
+    }
+
+    #[test]
+    fn test_custom_risk_rule() {
+        let mut ledger =
+            Ledger::new(Arc::default()).with_rule(Box::new(LargeDeposit), RiskAction::Lock);
+        ledger.apply(transaction(
+            TransactionType::Deposit(Decimal::new(500, 0)),
+            1,
+            1,
+        ));
+        let steps = ledger.apply(transaction(
+            TransactionType::Deposit(Decimal::new(5, 0)),
+            2,
+            1,
+        ));
+
+        assert_eq!(steps[0].outcome, Err(Rejection::AccountLocked));
+        assert_eq!(ledger.risk_events().len(), 1);
+        assert!(ledger
+            .account(&1.into(), None)
+            .unwrap()
+            .flags()
+            .contains("large_deposit"));
+    }
+}
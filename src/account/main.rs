@@ -1,28 +1,158 @@
-use super::{Transaction, TransactionSet, TransactionType};
+use super::{
+    AccountRow, Authorization, Balance, Breakdown, Conversion, Currency, Discrepancy, DisputeState,
+    ExpectedBalances, ExpiryAction, Identifier, Input, InputOptions, Ledger, OutputOptions, Policy,
+    RateTable, Rejection, Replay, Report, StatementRow, Transaction, TransactionSet,
+    TransactionType, WithdrawalDisputes,
+};
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use csv::Writer;
 use itertools::Itertools;
+use log::debug;
 use rust_decimal::prelude::*;
+use serde::Serializer;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+/// A checked change to an Account that has not been applied yet. Planning a change before
+/// applying it lets a transaction that spans two accounts apply to both or to neither.
+#[derive(Debug)]
+struct Change {
+    /// Change to the available funds.
+    available: Decimal,
+    /// Change to the funds held for disputes.
+    held: Decimal,
+    /// Change to the funds held for authorizations.
+    authorized: Decimal,
+    /// New state of the referenced authorization, if it changes. Authorizations with nothing
+    /// remaining are closed.
+    authorization: Option<Authorization>,
+    /// Amount of an authorization finalized as a withdrawal, if any.
+    captured: Option<Decimal>,
+    /// New amount of an opening hold, by the transaction identifier it is held under, if it
+    /// changes.
+    opening_hold: Option<(Identifier, Decimal)>,
+    /// New dispute state of the referenced transaction, if it changes.
+    dispute: Option<DisputeState>,
+    /// New lock state, if it changes.
+    locked: Option<bool>,
+    /// New closed state, if it changes.
+    closed: Option<bool>,
+}
+
+impl Default for Change {
+    fn default() -> Self {
+        Change {
+            available: Decimal::new(00, 1),
+            held: Decimal::new(00, 1),
+            authorized: Decimal::new(00, 1),
+            authorization: None,
+            captured: None,
+            opening_hold: None,
+            dispute: None,
+            locked: None,
+            closed: None,
+        }
+    }
+}
 
 /// A representation of known state for a given client identifier.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Account {
-    client: u16,
+    client: Identifier,
+    /// Currency of every amount on the account, if known.
+    currency: Option<Currency>,
     /// The total funds that are available. Equivalent to `total - held`.
     available: Decimal,
-    /// The total funds held for dispute. Equivalent to `total - available`.
+    /// The total funds held for disputes and authorizations. Equivalent to `total - available`.
     held: Decimal,
+    /// The part of `held` that is held for authorizations.
+    #[serde(skip)]
+    authorized: Decimal,
     /// The total funds in all states. Equivalent to `total + held`
     total: Decimal,
     /// Whether the account is locked as a result of a charge back.
     locked: bool,
+    /// Whether the account has been closed by an operator.
+    closed: bool,
+    /// Whether the available funds or the total are below zero. Accounts flagged here need to
+    /// be followed up by collections.
+    negative: bool,
+    /// Names of the risk rules that have flagged the account.
+    #[serde(serialize_with = "serialize_flags", skip_deserializing)]
+    flags: BTreeSet<String>,
     /// The set of transactions that compute the state of the account.
     #[serde(skip_serializing)]
     transactions: TransactionSet,
+    /// Totals of the applied transactions by category.
+    #[serde(skip)]
+    breakdown: Breakdown,
+    /// Open authorizations by transaction identifier.
+    #[serde(skip)]
+    authorizations: BTreeMap<Identifier, Authorization>,
+    /// The parts of `held` carried over from an opening balance, by the identifier of the
+    /// transaction they were held under in the other system.
+    #[serde(skip)]
+    opening_holds: BTreeMap<Identifier, Decimal>,
+    /// Dispute state of every transaction that has been disputed, by transaction identifier.
+    #[serde(skip)]
+    disputes: BTreeMap<Identifier, DisputeState>,
+    /// The rules the account applies to its transactions.
+    #[serde(skip)]
+    policy: Arc<Policy>,
 }
 
 impl Account {
+    /// Creates an Account with an empty transaction history and the default Policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client identifier
+    pub fn new(client: Identifier) -> Account {
+        Account::with_policy(client, Arc::default())
+    }
+
+    /// Creates an Account with an empty transaction history that applies the provided Policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client identifier
+    /// * `policy` - The rules the account applies to its transactions.
+    pub fn with_policy(client: Identifier, policy: Arc<Policy>) -> Account {
+        Account {
+            client: client.clone(),
+            currency: None,
+            available: Decimal::new(00, 1),
+            held: Decimal::new(00, 1),
+            authorized: Decimal::new(00, 1),
+            total: Decimal::new(00, 1),
+            locked: false,
+            closed: false,
+            negative: false,
+            flags: BTreeSet::new(),
+            transactions: TransactionSet {
+                transactions: Vec::new(),
+                client,
+            },
+            breakdown: Breakdown::default(),
+            authorizations: BTreeMap::new(),
+            opening_holds: BTreeMap::new(),
+            disputes: BTreeMap::new(),
+            policy,
+        }
+    }
+
+    /// Sets the currency of an Account that has no transaction history yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `currency` - Currency of every amount on the account, if known.
+    pub fn in_currency(mut self, currency: Option<Currency>) -> Account {
+        self.currency = currency;
+        self
+    }
+
     /// Generates Accounts with fully rendered states from provided CSV data and serializes them
     /// into a provided target that implements the `Write` trait.
     ///
@@ -30,25 +160,238 @@ impl Account {
     ///
     /// * `data` - Reference to a Vec<u8> buffer containing CSV data
     /// * `writer` - Anything that implements the Write trait.
-    pub fn accounts_state_from_csv_data(
-        data: &[u8],
+    pub fn accounts_state_from_csv_data(data: &[u8], writer: impl std::io::Write) -> Result<()> {
+        let input = Input::from_csv_data(data, &InputOptions::default())
+            .context("TransactionSet failed generation from the provided data")?;
+        Account::accounts_state_from_input(
+            input,
+            &Arc::default(),
+            &OutputOptions::default(),
+            writer,
+        )
+        .map(|_| ())
+    }
+
+    /// Generates Accounts with fully rendered states from transactions that have already been
+    /// read and serializes them into a provided target that implements the `Write` trait.
+    /// Returns every firing of a risk rule and every rejected transaction while the transactions
+    /// were applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Transactions read from source data, in application order.
+    /// * `policy` - The rules each account applies to its transactions.
+    /// * `options` - Controls the columns and currency of the output.
+    /// * `writer` - Anything that implements the Write trait.
+    pub fn accounts_state_from_input(
+        input: Input,
+        policy: &Arc<Policy>,
+        options: &OutputOptions,
         mut writer: impl std::io::Write,
-    ) -> Result<()> {
-        let transaction_sets = TransactionSet::transaction_sets_from_csv_data(data)
-            .with_context(|| format!("TransactionSet failed generation from the provided data"))?;
+    ) -> Result<Report> {
+        let now = input.latest_timestamp();
+        let mut ledger = Ledger::new(policy.clone()).paranoid(options.paranoid);
+        for transaction in input.transactions.into_iter() {
+            ledger.apply(transaction);
+        }
+        if let Some(now) = now {
+            ledger.advance_to(now);
+        }
+        Account::check_violations(&ledger)?;
+        let report = Report {
+            risk_events: ledger.risk_events().to_vec(),
+            rejections: ledger.rejections().to_vec(),
+        };
+        let accounts: Vec<Account> = match &options.conversion {
+            Some(conversion) => {
+                let mut converted = Vec::new();
+                for (client, group) in &ledger
+                    .into_accounts()
+                    .group_by(|account| account.client.clone())
+                {
+                    converted.push(Account::converted(client, group, conversion)?);
+                }
+                converted
+            }
+            None => ledger.into_accounts().collect(),
+        };
+        // The currency column is only written for input that uses currencies, so that the output
+        // of single currency input keeps its columns.
+        let currency = accounts.iter().any(|account| account.currency.is_some());
         let mut csv_writer = Writer::from_writer(vec![]);
-        for transaction_set in transaction_sets.into_iter() {
-            let account = Account::from_transaction_set(transaction_set);
-            csv_writer
-                .serialize(account)
-                .with_context(|| format!("Failed to serialize account data to CSV writer."))?;
+        for account in accounts.iter() {
+            let row = AccountRow::new(account, currency, options.status);
+            if options.breakdown {
+                csv_writer.serialize((row, &account.breakdown))
+            } else {
+                csv_writer.serialize(row)
+            }
+            .context("Failed to serialize account data to CSV writer.")?;
         }
         let wrtr = csv_writer
             .into_inner()
-            .with_context(|| format!("CSV writer data failed to flush internal buffer."))?;
-        let data = String::from_utf8(wrtr)
-            .with_context(|| format!("Failed to generate UTF-8 from writer buffer."))?;
-        writeln!(writer, "{}", data).with_context(|| format!("Writer failed to write results."))
+            .context("CSV writer data failed to flush internal buffer.")?;
+        let data =
+            String::from_utf8(wrtr).context("Failed to generate UTF-8 from writer buffer.")?;
+        writeln!(writer, "{}", data).context("Writer failed to write results.")?;
+        Ok(report)
+    }
+
+    /// Writes the full timeline of a single client's account as CSV into a provided target that
+    /// implements the `Write` trait. Each row holds a transaction, whether it was applied or
+    /// rejected and why, and the account balance directly after it.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Transactions read from source data, in application order.
+    /// * `client` - Client identifier of the account to explain
+    /// * `policy` - The rules the account applies to its transactions.
+    /// * `paranoid` - Whether to check balance invariants in release builds.
+    /// * `writer` - Anything that implements the Write trait.
+    pub fn statement_from_input(
+        input: Input,
+        client: Identifier,
+        policy: &Arc<Policy>,
+        paranoid: bool,
+        writer: impl std::io::Write,
+    ) -> Result<()> {
+        let now = input.latest_timestamp();
+        let mut ledger = Ledger::new(policy.clone()).paranoid(paranoid);
+        let mut steps = Vec::new();
+        for transaction in input.transactions.into_iter() {
+            steps.extend(
+                ledger
+                    .apply(transaction)
+                    .into_iter()
+                    .filter(|step| step.client == client),
+            );
+        }
+        if steps.is_empty() {
+            anyhow::bail!("No transactions found for client {}", client);
+        }
+        if let Some(now) = now {
+            steps.extend(
+                ledger
+                    .advance_to(now)
+                    .into_iter()
+                    .filter(|step| step.client == client),
+            );
+        }
+        Account::check_violations(&ledger)?;
+        let mut csv_writer = Writer::from_writer(writer);
+        for step in steps {
+            csv_writer
+                .serialize(StatementRow::from(&step))
+                .context("Failed to serialize statement data to CSV writer.")?;
+        }
+        csv_writer
+            .flush()
+            .context("Writer failed to write statement.")
+    }
+
+    /// Compares the accounts rendered from transactions with expected closing balances and
+    /// writes every difference as CSV into a provided target that implements the `Write` trait.
+    /// Returns the differences, which are empty when the balances reconcile.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Transactions read from source data, in application order.
+    /// * `policy` - The rules each account applies to its transactions.
+    /// * `expected` - The closing balances the accounts should have.
+    /// * `tolerance` - The largest difference between amounts that still counts as a match.
+    /// * `paranoid` - Whether to check balance invariants in release builds.
+    /// * `writer` - Anything that implements the Write trait.
+    pub fn reconcile_input(
+        input: Input,
+        policy: &Arc<Policy>,
+        expected: &ExpectedBalances,
+        tolerance: Decimal,
+        paranoid: bool,
+        writer: impl std::io::Write,
+    ) -> Result<Vec<Discrepancy>> {
+        let now = input.latest_timestamp();
+        let mut ledger = Ledger::new(policy.clone()).paranoid(paranoid);
+        for transaction in input.transactions.into_iter() {
+            ledger.apply(transaction);
+        }
+        if let Some(now) = now {
+            ledger.advance_to(now);
+        }
+        Account::check_violations(&ledger)?;
+        let actual: BTreeMap<(Identifier, Option<Currency>), Balance> = ledger
+            .into_accounts()
+            .map(|account| {
+                let balance = account.balance();
+                ((account.client, account.currency), balance)
+            })
+            .collect();
+        let discrepancies = expected.reconcile(&actual, tolerance);
+        Discrepancy::write_csv(&discrepancies, writer)?;
+        Ok(discrepancies)
+    }
+
+    /// Fails if any transaction applied by the Ledger broke a balance invariant, so that a
+    /// corrupted balance never reaches the output.
+    ///
+    /// # Arguments
+    ///
+    /// * `ledger` - The Ledger the transactions were applied through.
+    fn check_violations(ledger: &Ledger) -> Result<()> {
+        match ledger.violations().first() {
+            Some(violation) => Err(violation.clone()).with_context(|| {
+                format!(
+                    "{} transactions broke balance invariants.",
+                    ledger.violations().len()
+                )
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Combines a client's accounts in every currency into a single account in the currency of the
+    /// provided Conversion. Converted amounts are rounded to four decimal places. The result is
+    /// locked if any of the accounts is locked and closed if all of them are closed. It has no
+    /// transaction history.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client identifier
+    /// * `accounts` - The client's accounts.
+    /// * `conversion` - The currency to convert into and the rates to use.
+    fn converted(
+        client: Identifier,
+        accounts: impl Iterator<Item = Account>,
+        conversion: &Conversion,
+    ) -> Result<Account> {
+        let mut converted =
+            Account::new(client.clone()).in_currency(Some(conversion.currency.clone()));
+        converted.closed = true;
+        for account in accounts {
+            let currency = account.currency.as_ref().with_context(|| {
+                format!(
+                    "Cannot convert the balance of client {} without a currency.",
+                    client
+                )
+            })?;
+            let rate = conversion
+                .rates
+                .rate(currency, &conversion.currency)
+                .with_context(|| {
+                    format!(
+                        "No exchange rate from {} to {}.",
+                        currency, conversion.currency
+                    )
+                })?;
+            converted.available += RateTable::convert(account.available, rate);
+            converted.held += RateTable::convert(account.held, rate);
+            converted.authorized += RateTable::convert(account.authorized, rate);
+            converted.locked |= account.locked;
+            converted.flags.extend(account.flags);
+            converted.closed &= account.closed;
+            converted.breakdown.add_converted(&account.breakdown, rate);
+        }
+        converted.update_total();
+        Ok(converted)
     }
 
     /// Generates an Account with a fully rendered state from a TransactionSet.
@@ -56,272 +399,1185 @@ impl Account {
     /// # Arguments
     ///
     /// * `transaction_set` - A series of transactions with a shared client identifier in
-    /// chronological order.
+    ///   chronological order.
     pub fn from_transaction_set(transaction_set: TransactionSet) -> Account {
-        let mut account = Account {
-            client: transaction_set.client,
-            available: Decimal::new(00, 1),
-            held: Decimal::new(00, 1),
-            total: Decimal::new(00, 1),
-            locked: false,
-            transactions: TransactionSet {
-                transactions: Vec::new(),
-                client: transaction_set.client,
-            },
-        };
+        Account::from_transaction_set_with_policy(transaction_set, Arc::default())
+    }
+
+    /// Generates an Account that applies the provided Policy with a fully rendered state from a
+    /// TransactionSet.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction_set` - A series of transactions with a shared client identifier in
+    ///   chronological order.
+    /// * `policy` - The rules the account applies to its transactions.
+    pub fn from_transaction_set_with_policy(
+        transaction_set: TransactionSet,
+        policy: Arc<Policy>,
+    ) -> Account {
+        let mut account = Account::with_policy(transaction_set.client, policy);
 
         for transaction in transaction_set.transactions.into_iter() {
-            account.resolve_new_transaction(transaction);
+            let tx = transaction.tx.clone();
+            if let Err(rejection) = account.resolve_new_transaction(transaction) {
+                debug!(
+                    "Client {} rejected tx {}: {}",
+                    account.client, tx, rejection
+                );
+            }
         }
         account
     }
 
+    /// Walks a TransactionSet one transaction at a time, starting from an empty account and
+    /// yielding the balance after each step.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction_set` - A series of transactions with a shared client identifier in
+    ///   chronological order.
+    pub fn replay(transaction_set: TransactionSet) -> Replay {
+        Replay::new(
+            Account::new(transaction_set.client),
+            transaction_set.transactions,
+        )
+    }
+
+    /// Client identifier
+    pub fn client(&self) -> &Identifier {
+        &self.client
+    }
+
+    /// Currency of every amount on the account, if known.
+    pub fn currency(&self) -> Option<&Currency> {
+        self.currency.as_ref()
+    }
+
+    /// The current funds and lock state of the account.
+    pub fn balance(&self) -> Balance {
+        Balance {
+            available: self.available,
+            held: self.held,
+            total: self.total,
+            authorized: self.authorized,
+            locked: self.locked,
+            closed: self.closed,
+            negative: self.negative,
+        }
+    }
+
+    /// Totals of the transactions applied to the account, by category.
+    pub fn breakdown(&self) -> &Breakdown {
+        &self.breakdown
+    }
+
+    /// The dispute state of a transaction, if it has ever been disputed.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - Transaction identifier
+    pub fn dispute_state(&self, tx: &Identifier) -> Option<&DisputeState> {
+        self.disputes.get(tx)
+    }
+
+    /// Names of the risk rules that have flagged the account.
+    pub fn flags(&self) -> &BTreeSet<String> {
+        &self.flags
+    }
+
+    /// Flags the account on behalf of a risk rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `rule` - Name of the rule that fired.
+    pub fn flag(&mut self, rule: &str) {
+        self.flags.insert(String::from(rule));
+    }
+
+    /// Locks the account pre-emptively on behalf of a risk rule. Like a chargeback lock, it can
+    /// only be lifted by an unlock transaction.
+    pub fn lock(&mut self) {
+        self.locked = true;
+    }
+
+    /// The transactions that have been applied to the account, in order.
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions.transactions
+    }
+
     /// Allows the addition of any new transaction to the history of an account. The transaction is
     /// applied to the Account state and appended to the TransactionSet for the Account. Locked
-    /// accounts cannot process transactions.
+    /// accounts cannot process transactions other than an unlock, and closed accounts cannot
+    /// process transactions other than a reopen. A transaction that cannot be applied leaves the
+    /// Account untouched and the reason is returned. Transfers and disputes of transfers involve a
+    /// second account and are rejected here; they are applied with
+    /// `Account::resolve_paired_transaction`.
     ///
     /// # Arguments
     ///
     /// * `transaction` - A transaction of any TransactionType
-    pub fn resolve_new_transaction(&mut self, transaction: Transaction) {
-        // If the provided transaction is not for this client, ignore it.
-        if self.client != transaction.client || self.locked {
-            ()
+    pub fn resolve_new_transaction(&mut self, transaction: Transaction) -> Result<(), Rejection> {
+        if self.client != transaction.client {
+            return Err(Rejection::WrongClient);
         }
-        match transaction.transaction_type {
-            TransactionType::Deposit(_) => self.deposit(transaction),
-            TransactionType::Withdraw(_) => self.withdraw(transaction),
-            TransactionType::Dispute => self.dispute(transaction),
-            TransactionType::Resolve => self.resolve(transaction),
-            TransactionType::Chargeback => self.chargeback(transaction),
+        if let Some(timestamp) = transaction.timestamp {
+            self.advance_to(timestamp);
+        }
+        self.check_currency(&transaction)?;
+        self.check_state(&transaction)?;
+        if self.requires_counterparty(&transaction) {
+            return Err(Rejection::CounterpartyRequired);
+        }
+        self.apply(transaction)
+    }
+
+    /// Applies a transaction that involves two accounts to both of them or to neither. A transfer
+    /// debits the source account and credits the destination account. A dispute, resolve or
+    /// chargeback of a transfer acts on both sides of it: the destination is treated as if it had
+    /// received a deposit and the source as if it had made a withdrawal.
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - The account of the client named by the transaction. The source of a transfer.
+    /// * `second` - The other account involved. The destination of a transfer.
+    /// * `transaction` - A transfer, or a dispute, resolve or chargeback of one
+    pub fn resolve_paired_transaction(
+        first: &mut Account,
+        second: &mut Account,
+        transaction: Transaction,
+    ) -> Result<(), Rejection> {
+        if first.client != transaction.client || first.client == second.client {
+            return Err(Rejection::WrongClient);
+        }
+        // Each account records the dispute family under its own client identifier.
+        let leg = |account: &Account| match transaction.transaction_type {
+            TransactionType::Transfer { .. } => transaction.clone(),
+            _ => Transaction {
+                client: account.client.clone(),
+                ..transaction.clone()
+            },
+        };
+        let (first_leg, second_leg) = (leg(first), leg(second));
+        if let Some(timestamp) = transaction.timestamp {
+            first.advance_to(timestamp);
+            second.advance_to(timestamp);
+        }
+        first.check_currency(&first_leg)?;
+        second.check_currency(&second_leg)?;
+        first.check_state(&first_leg)?;
+        second.check_state(&second_leg)?;
+        if !first.requires_counterparty(&first_leg) || !second.requires_counterparty(&second_leg) {
+            return Err(Rejection::WrongClient);
         }
+        let first_change = first.plan(&first_leg)?;
+        let second_change = second.plan(&second_leg)?;
+        first.commit(first_change, first_leg);
+        second.commit(second_change, second_leg);
+        Ok(())
     }
 
-    /// Execute a deposit transaction on the Account state. This increases the available amount,
-    /// recalculates the total, and pushes the transaction to the Account's TransactionSet.
+    /// Checks that the transaction is in the currency of the account. Transactions without a
+    /// currency are taken to be in the account's currency.
     ///
     /// # Arguments
     ///
     /// * `transaction` - A transaction of any TransactionType
-    fn deposit(&mut self, transaction: Transaction) {
-        match transaction.transaction_type {
-            TransactionType::Deposit(amount) => {
-                self.available = self.available + amount;
-                self.total = self.held + self.available;
-                self.transactions.transactions.push(transaction);
+    fn check_currency(&self, transaction: &Transaction) -> Result<(), Rejection> {
+        match &transaction.currency {
+            Some(currency) if Some(currency) != self.currency.as_ref() => {
+                Err(Rejection::CurrencyMismatch)
             }
-            _ => (),
+            _ => Ok(()),
         }
     }
 
-    /// Execute a withdraw transaction on the Account state. This decreases the available amount,
-    /// recalculates the total, and pushes the transaction to the Account's TransactionSet.
+    /// Checks that the account is open to the provided transaction. Closed accounts only accept
+    /// a reopen and locked accounts only accept an unlock.
     ///
     /// # Arguments
     ///
     /// * `transaction` - A transaction of any TransactionType
-    fn withdraw(&mut self, transaction: Transaction) {
+    fn check_state(&self, transaction: &Transaction) -> Result<(), Rejection> {
         match transaction.transaction_type {
-            TransactionType::Withdraw(amount) => {
-                if amount <= self.available {
-                    self.available = self.available - amount;
-                    self.total = self.held + self.available;
-                    self.transactions.transactions.push(transaction);
-                }
-            }
-            _ => (),
+            TransactionType::Reopen(_) => Ok(()),
+            _ if self.closed => Err(Rejection::AccountClosed),
+            TransactionType::Unlock(_) => Ok(()),
+            _ if self.locked => Err(Rejection::AccountLocked),
+            _ => Ok(()),
         }
     }
 
-    /// Execute a dispute transaction on the Account state. This moves the amount from a withdraw
-    /// or deposit transaction into the `held` amount on the Account, changing the available amount,
-    /// but not the total.
+    /// Indicates whether a transaction involves a second account: a transfer, or a dispute,
+    /// resolve or chargeback of one.
     ///
     /// # Arguments
     ///
     /// * `transaction` - A transaction of any TransactionType
-    fn dispute(&mut self, transaction: Transaction) {
+    fn requires_counterparty(&self, transaction: &Transaction) -> bool {
         match transaction.transaction_type {
-            TransactionType::Dispute => {
-                let disputed_transaction = self.get_transaction(transaction.tx);
-                if let Some(txn) = disputed_transaction {
-                    match txn.transaction_type {
-                        TransactionType::Deposit(amount) => {
-                            self.available = self.available - amount;
-                            self.held = self.held + amount;
-                        }
-                        TransactionType::Withdraw(amount) => {
-                            self.available = self.available + amount;
-                            self.held = self.held - amount;
-                        }
-                        _ => (),
-                    };
-                    self.total = self.held + self.available;
-                    self.transactions.transactions.push(transaction);
-                }
+            TransactionType::Transfer { .. } => true,
+            TransactionType::Dispute(_)
+            | TransactionType::Resolve(_)
+            | TransactionType::Chargeback(_) => {
+                self.get_transaction(&transaction.tx).is_some_and(|txn| {
+                    matches!(txn.transaction_type, TransactionType::Transfer { .. })
+                })
             }
-            _ => (),
+            _ => false,
         }
     }
 
-    /// Execute a resolve transaction on the Account state. This moves the amount from held that
-    /// that was palced there during a dispute transaction. This changes the available amount,
-    /// but not the total. If there is no dispute in the TransactionSet for the specified resolve
-    /// there is no effect.
+    /// Applies a transaction to the Account state without checking whether the account is locked
+    /// or closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn apply(&mut self, transaction: Transaction) -> Result<(), Rejection> {
+        let change = self.plan(&transaction)?;
+        self.commit(change, transaction);
+        Ok(())
+    }
+
+    /// Works out how a transaction changes the Account state without changing it.
     ///
     /// # Arguments
     ///
     /// * `transaction` - A transaction of any TransactionType
-    fn resolve(&mut self, transaction: Transaction) {
+    fn plan(&self, transaction: &Transaction) -> Result<Change, Rejection> {
         match transaction.transaction_type {
-            TransactionType::Resolve => {
-                if let Some(txn) = self.get_transaction(transaction.tx) {
-                    if self.transaction_disputed(txn) {
-                        match txn.transaction_type {
-                            TransactionType::Deposit(amount) => {
-                                self.available = self.available + amount;
-                                self.held = self.held - amount;
-                            }
-                            TransactionType::Withdraw(amount) => {
-                                self.available = self.available - amount;
-                                self.held = self.held + amount;
-                            }
-                            _ => (),
-                        };
-                        self.total = self.held + self.available;
-                        self.transactions.transactions.push(transaction);
-                    }
-                }
+            TransactionType::Deposit(_) => self.deposit(transaction),
+            TransactionType::Withdraw(_) => self.withdraw(transaction),
+            TransactionType::Transfer { .. } => self.transfer(transaction),
+            TransactionType::Fee(_) => self.fee(transaction),
+            TransactionType::Interest(_) | TransactionType::Adjustment { .. } => {
+                self.credit(transaction)
+            }
+            TransactionType::Authorize(_) => self.authorize(transaction),
+            TransactionType::Capture(_) => self.capture(transaction),
+            TransactionType::Void => self.void(transaction),
+            TransactionType::Dispute(_) => self.dispute(transaction),
+            TransactionType::Resolve(_) => self.resolve(transaction),
+            TransactionType::Chargeback(_) => self.chargeback(transaction),
+            TransactionType::Unlock(_) => self.unlock(transaction),
+            TransactionType::Close(_) => self.close(transaction),
+            TransactionType::Reopen(_) => self.reopen(transaction),
+            TransactionType::Opening { .. } => self.opening(transaction),
+        }
+    }
+
+    /// Applies a planned Change to the Account state, recalculates the total, and pushes the
+    /// transaction to the Account's TransactionSet.
+    ///
+    /// # Arguments
+    ///
+    /// * `change` - The change planned for the transaction.
+    /// * `transaction` - The transaction the change was planned for.
+    fn commit(&mut self, change: Change, transaction: Transaction) {
+        self.available += change.available;
+        self.held += change.held + change.authorized;
+        self.authorized += change.authorized;
+        self.update_total();
+        if let Some(authorization) = change.authorization {
+            if authorization.is_open() {
+                self.authorizations
+                    .insert(transaction.tx.clone(), authorization);
+            } else {
+                self.authorizations.remove(&transaction.tx);
             }
-            _ => (),
         }
+        if let Some(captured) = change.captured {
+            self.breakdown.withdrawals += captured;
+        }
+        if let Some((tx, amount)) = change.opening_hold {
+            self.opening_holds.insert(tx, amount);
+        }
+        if let Some(state) = change.dispute {
+            self.disputes.insert(transaction.tx.clone(), state);
+        }
+        if let Some(locked) = change.locked {
+            self.locked = locked;
+        }
+        if let Some(closed) = change.closed {
+            self.closed = closed;
+        }
+        self.breakdown
+            .record(&self.client, &transaction.transaction_type);
+        self.transactions.transactions.push(transaction);
     }
 
-    /// Execute a chargeback transaction on the Account state. This finalizes a dispute rather than
-    /// resolving it and results in an account lock.
+    /// Plan a deposit transaction. This increases the available amount.
     ///
     /// # Arguments
     ///
     /// * `transaction` - A transaction of any TransactionType
-    fn chargeback(&mut self, transaction: Transaction) {
-        // This solution uses clone and a strange code structure to avoid having to use
-        // any unsafe code despite needing what is otherwise a simultaneous mutable and
-        // immutable borrow for get_transaction and resolve.
+    fn deposit(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        if self.is_used(&transaction.tx) {
+            return Err(Rejection::DuplicateTransaction);
+        }
+        self.check_limits(transaction)?;
+        match transaction.transaction_type {
+            TransactionType::Deposit(amount) => Ok(Change {
+                available: amount,
+                ..Change::default()
+            }),
+            _ => Ok(Change::default()),
+        }
+    }
 
-        // If the account has no unresolved disputes, there is nothing to chargeback.
-        if !self.has_unresolved_disputes() {
-            ()
+    /// Plan a withdraw transaction. This decreases the available amount, which may not drop below
+    /// the client's overdraft limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn withdraw(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        if self.is_used(&transaction.tx) {
+            return Err(Rejection::DuplicateTransaction);
         }
-        let mut txn_for_resolution: Option<Transaction> = None;
+        self.check_limits(transaction)?;
         match transaction.transaction_type {
-            TransactionType::Chargeback => {
-                if let Some(txn) = self.get_transaction(transaction.tx) {
-                    let break_reference = txn.clone();
-                    if self.transaction_disputed(&break_reference) {
-                        txn_for_resolution = Some(break_reference.clone());
-                    }
-                }
-            }
-            _ => (),
-        };
-        if let Some(txn) = txn_for_resolution {
-            self.resolve(txn);
+            TransactionType::Withdraw(amount) => self.debit(amount),
+            _ => Ok(Change::default()),
         }
-        self.transactions.transactions.push(transaction);
-        self.locked = true;
     }
 
-    /// Indicated whether a given transaction is disputed.
+    /// Checks a transaction that moves funds against the client's limits in the Policy. Outflows
+    /// are checked against the withdrawal limits and inflows against the balance limit. Limits
+    /// over a window of time count the transactions already applied within the window ending at
+    /// the transaction's timestamp, and do not apply to transactions without one.
     ///
     /// # Arguments
     ///
     /// * `transaction` - A transaction of any TransactionType
-    fn transaction_disputed(&self, transaction: &Transaction) -> bool {
-        if let TransactionType::Dispute = transaction.transaction_type {
-            false
+    fn check_limits(&self, transaction: &Transaction) -> Result<(), Rejection> {
+        let limits = self.policy.limits.for_client(&self.client);
+        if let Some(amount) = self.outflow(transaction) {
+            if limits.max_withdrawal.is_some_and(|limit| amount > limit) {
+                return Err(Rejection::ExceedsWithdrawalLimit);
+            }
+            if let Some(timestamp) = transaction.timestamp {
+                if let Some(limit) = limits.daily_withdrawal {
+                    let day = timestamp.date_naive();
+                    let withdrawn =
+                        self.withdrawn_within(|time| time.date_naive() == day && time <= timestamp);
+                    if withdrawn + amount > limit {
+                        return Err(Rejection::ExceedsDailyWithdrawalLimit);
+                    }
+                }
+                if let Some(limit) = &limits.rolling_withdrawal {
+                    let start = timestamp - limit.window();
+                    let withdrawn = self.withdrawn_within(|time| time > start && time <= timestamp);
+                    if withdrawn + amount > limit.amount {
+                        return Err(Rejection::ExceedsRollingWithdrawalLimit);
+                    }
+                }
+            }
+        } else if let Some(amount) = self.inflow(transaction) {
+            if limits
+                .max_balance
+                .is_some_and(|limit| self.total + amount > limit)
+            {
+                return Err(Rejection::ExceedsBalanceLimit);
+            }
         } else {
-            let related_transactions = self
+            return Ok(());
+        }
+        if let (Some(limit), Some(timestamp)) = (&limits.max_transactions, transaction.timestamp) {
+            let start = timestamp - limit.window();
+            let count = self
                 .transactions
                 .transactions
                 .iter()
-                .filter(|txn| txn.tx == transaction.tx)
-                .sorted_by_key(|txn| txn.tx)
-                .group_by(|txn| txn.transaction_type);
-            let mut disputes = 0;
-            let mut resolutions = 0;
-            for (key, group) in &related_transactions {
-                let count = group.count();
-                if key == TransactionType::Dispute {
-                    disputes = count;
-                }
-                if key == TransactionType::Resolve {
-                    resolutions = count;
-                }
+                .filter(|txn| self.outflow(txn).is_some() || self.inflow(txn).is_some())
+                .filter(|txn| {
+                    txn.timestamp
+                        .is_some_and(|time| time > start && time <= timestamp)
+                })
+                .count();
+            if count >= limit.count as usize {
+                return Err(Rejection::TooManyTransactions);
             }
-            disputes > resolutions
         }
+        Ok(())
     }
 
-    /// Indicates whether the account has unresolved disputes.
+    /// The amount a transaction takes out of this account's available funds: a withdrawal, an
+    /// outgoing transfer, a fee, an authorization or a negative adjustment. A capture only
+    /// finalizes funds its authorization already took, so it has no outflow of its own.
     ///
     /// # Arguments
     ///
     /// * `transaction` - A transaction of any TransactionType
-    fn has_unresolved_disputes(&self) -> bool {
-        let disputes = self
-            .transactions
-            .transactions
-            .iter()
-            .filter(|txn| match txn.transaction_type {
-                TransactionType::Dispute => true,
-                _ => false,
-            })
-            .count();
-        let resolutions = self
-            .transactions
-            .transactions
-            .iter()
-            .filter(|txn| match txn.transaction_type {
-                TransactionType::Resolve => true,
-                _ => false,
-            })
-            .count();
-        disputes > resolutions
+    fn outflow(&self, transaction: &Transaction) -> Option<Decimal> {
+        match &transaction.transaction_type {
+            TransactionType::Withdraw(amount)
+            | TransactionType::Fee(amount)
+            | TransactionType::Authorize(amount) => Some(*amount),
+            TransactionType::Transfer {
+                destination,
+                amount,
+            } if *destination != self.client => Some(*amount),
+            TransactionType::Adjustment { amount, .. } if *amount < Decimal::zero() => {
+                Some(-*amount)
+            }
+            _ => None,
+        }
     }
 
-    /// Returns a transaction by its transaction identifier.
+    /// The amount a transaction adds to this account's available funds: a deposit, an incoming
+    /// transfer, interest or a positive adjustment.
     ///
     /// # Arguments
     ///
     /// * `transaction` - A transaction of any TransactionType
-    fn get_transaction(&self, identifier: u32) -> Option<&Transaction> {
-        let mut target_transaction = self
-            .transactions
+    fn inflow(&self, transaction: &Transaction) -> Option<Decimal> {
+        match &transaction.transaction_type {
+            TransactionType::Deposit(amount) | TransactionType::Interest(amount) => Some(*amount),
+            TransactionType::Transfer {
+                destination,
+                amount,
+            } if *destination == self.client => Some(*amount),
+            TransactionType::Adjustment { amount, .. } if *amount > Decimal::zero() => {
+                Some(*amount)
+            }
+            _ => None,
+        }
+    }
+
+    /// The total of the applied outflows whose timestamps match the provided window.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - Indicates whether a timestamp falls within the window.
+    fn withdrawn_within(&self, window: impl Fn(DateTime<Utc>) -> bool) -> Decimal {
+        self.transactions
             .transactions
             .iter()
-            .filter(|txn| txn.tx == identifier);
-        target_transaction.next()
+            .filter(|txn| txn.timestamp.is_some_and(&window))
+            .filter_map(|txn| self.outflow(txn))
+            .sum()
     }
-}
-
-// Tests
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::error::Error;
-    use std::str;
-
-    #[test]
-    fn test_from_csv_data() -> Result<(), Box<dyn Error>> {
-        let mut result = Vec::new();
-        let sample_input = std::fs::read("test_data/sample_input.csv").unwrap();
-        let sample_output = std::fs::read_to_string("test_data/sample_output.csv").unwrap();
 
-        Account::accounts_state_from_csv_data(&sample_input, &mut result)?;
+    /// Plan one side of a transfer. The source account is debited like a withdraw, and the
+    /// destination account is credited like a deposit.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn transfer(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        if self.is_used(&transaction.tx) {
+            return Err(Rejection::DuplicateTransaction);
+        }
+        match &transaction.transaction_type {
+            TransactionType::Transfer {
+                destination,
+                amount,
+            } => {
+                let amount = *amount;
+                if *destination == transaction.client {
+                    Err(Rejection::SelfTransfer)
+                } else if self.client == transaction.client {
+                    self.check_limits(transaction)?;
+                    self.debit(amount)
+                } else if self.client == *destination {
+                    self.check_limits(transaction)?;
+                    Ok(Change {
+                        available: amount,
+                        ..Change::default()
+                    })
+                } else {
+                    Err(Rejection::WrongClient)
+                }
+            }
+            _ => Ok(Change::default()),
+        }
+    }
 
-        assert_eq!(str::from_utf8(&result).unwrap(), sample_output);
-        Ok(())
+    /// Plan a fee transaction. This decreases the available amount, which may only drop below the
+    /// client's overdraft limit if the fee Policy allows it.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn fee(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        if self.is_used(&transaction.tx) {
+            return Err(Rejection::DuplicateTransaction);
+        }
+        self.check_limits(transaction)?;
+        match transaction.transaction_type {
+            TransactionType::Fee(amount) if self.policy.fees.allow_negative => Ok(Change {
+                available: -amount,
+                ..Change::default()
+            }),
+            TransactionType::Fee(amount) => self.debit(amount),
+            _ => Ok(Change::default()),
+        }
+    }
+
+    /// Plan an interest or adjustment transaction. This adds the amount to the available funds
+    /// without checking the overdraft limit; a negative adjustment removes funds.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn credit(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        if self.is_used(&transaction.tx) {
+            return Err(Rejection::DuplicateTransaction);
+        }
+        self.check_limits(transaction)?;
+        Ok(Change {
+            available: transaction
+                .transaction_type
+                .amount()
+                .unwrap_or_else(|| Decimal::new(00, 1)),
+            ..Change::default()
+        })
+    }
+
+    /// Plan an authorize transaction. This moves the amount from available into held, where it is
+    /// accounted separately from disputes until it is captured, voided or expires. The available
+    /// amount may not drop below the client's overdraft limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn authorize(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        if self.is_used(&transaction.tx) {
+            return Err(Rejection::DuplicateTransaction);
+        }
+        self.check_limits(transaction)?;
+        match transaction.transaction_type {
+            TransactionType::Authorize(amount) => Ok(Change {
+                authorized: amount,
+                authorization: Some(Authorization {
+                    remaining: amount,
+                    opened: transaction.timestamp,
+                }),
+                ..self.debit(amount)?
+            }),
+            _ => Ok(Change::default()),
+        }
+    }
+
+    /// Plan a capture transaction. This removes the captured amount from held, finalizing it as a
+    /// withdrawal. Only the given portion is captured if the capture carries an amount; the rest
+    /// stays held.
+    /// Captures are not checked against the limits, because the authorization already was.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn capture(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        let mut authorization = self.open_authorization(transaction)?;
+        let amount = transaction
+            .transaction_type
+            .amount()
+            .unwrap_or(authorization.remaining);
+        if amount > authorization.remaining {
+            return Err(Rejection::ExceedsAuthorizedAmount);
+        }
+        authorization.remaining -= amount;
+        Ok(Change {
+            authorized: -amount,
+            authorization: Some(authorization),
+            captured: Some(amount),
+            ..Change::default()
+        })
+    }
+
+    /// Plan a void transaction. This moves what remains of an authorization from held back to
+    /// available.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn void(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        let mut authorization = self.open_authorization(transaction)?;
+        let amount = authorization.remaining;
+        authorization.remaining -= amount;
+        Ok(Change {
+            available: amount,
+            authorized: -amount,
+            authorization: Some(authorization),
+            ..Change::default()
+        })
+    }
+
+    /// Looks up the open authorization a capture or void refers to.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A capture or void transaction
+    fn open_authorization(&self, transaction: &Transaction) -> Result<Authorization, Rejection> {
+        self.get_transaction(&transaction.tx)
+            .ok_or(Rejection::UnknownTransaction)?;
+        self.authorizations
+            .get(&transaction.tx)
+            .cloned()
+            .ok_or(Rejection::NotAuthorized)
+    }
+
+    /// Plan the removal of an amount from the available funds, which may not drop below the
+    /// client's overdraft limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - The amount to remove.
+    fn debit(&self, amount: Decimal) -> Result<Change, Rejection> {
+        if self.available - amount < self.policy.overdraft.floor(&self.client) {
+            return Err(Rejection::InsufficientFunds);
+        }
+        Ok(Change {
+            available: -amount,
+            ..Change::default()
+        })
+    }
+
+    /// Plan a dispute transaction. A disputed deposit moves its amount from available into
+    /// `held`, changing the available amount but not the total. A disputed withdraw is handled
+    /// according to the withdrawal dispute Policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn dispute(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        if self.opening_holds.contains_key(&transaction.tx) {
+            return Err(Rejection::NotDisputable);
+        }
+        let txn = self
+            .get_transaction(&transaction.tx)
+            .ok_or(Rejection::UnknownTransaction)?;
+        let (original_amount, credit) = self.disputed_leg(txn).ok_or(Rejection::NotDisputable)?;
+        let mut state = self
+            .disputes
+            .get(&transaction.tx)
+            .cloned()
+            .unwrap_or_else(|| DisputeState::new(original_amount));
+        if state.disputable <= Decimal::zero() {
+            return Err(if state.is_open() {
+                Rejection::AlreadyDisputed
+            } else {
+                Rejection::ExceedsDisputableAmount
+            });
+        }
+        let amount = transaction
+            .transaction_type
+            .amount()
+            .unwrap_or(state.disputable);
+        if amount > state.disputable {
+            return Err(Rejection::ExceedsDisputableAmount);
+        }
+        if let (Some(window), Some(opened), Some(original)) = (
+            self.policy.disputes.window(),
+            transaction.timestamp,
+            txn.timestamp,
+        ) {
+            if opened - original > window {
+                return Err(Rejection::DisputeWindowClosed);
+            }
+        }
+        let mut change = Change::default();
+        if credit {
+            if !self.policy.overdraft.allow_negative_after_dispute
+                && self.available - amount < self.policy.overdraft.floor(&self.client)
+            {
+                return Err(Rejection::InsufficientFunds);
+            }
+            change.available = -amount;
+            change.held = amount;
+        } else {
+            match self.policy.disputes.withdrawals {
+                WithdrawalDisputes::Disallow => return Err(Rejection::NotDisputable),
+                WithdrawalDisputes::ReverseCredit => change.available = amount,
+                WithdrawalDisputes::HoldAsPendingCredit => change.held = amount,
+            }
+        }
+        if !state.is_open() {
+            state.opened = transaction.timestamp;
+        }
+        state.disputed += amount;
+        state.disputable -= amount;
+        change.dispute = Some(state);
+        Ok(change)
+    }
+
+    /// Plan a resolve transaction. This undoes the effect of the dispute it closes: for a deposit
+    /// the amount moves from held back to available, changing the available amount but not the
+    /// total. Only the given portion of the disputed amount is released if the resolve carries an
+    /// amount; that portion may be disputed again. If there is no dispute in the TransactionSet
+    /// for the specified resolve the transaction is rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn resolve(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        if self.opening_holds.contains_key(&transaction.tx) {
+            return self.release_opening_hold(transaction);
+        }
+        let (credit, mut state, amount) = self.open_dispute(transaction)?;
+        let mut change = Change::default();
+        if credit {
+            change.available = amount;
+            change.held = -amount;
+        } else {
+            match self.policy.disputes.withdrawals {
+                WithdrawalDisputes::Disallow => (),
+                WithdrawalDisputes::ReverseCredit => change.available = -amount,
+                WithdrawalDisputes::HoldAsPendingCredit => change.held = -amount,
+            }
+        }
+        state.disputed -= amount;
+        state.disputable += amount;
+        if !state.is_open() {
+            state.opened = None;
+        }
+        change.dispute = Some(state);
+        Ok(change)
+    }
+
+    /// Plan a chargeback transaction. This finalizes a dispute rather than resolving it and
+    /// results in an account lock. For a deposit the held amount leaves the account, changing the
+    /// total but not the available amount. For a withdraw the reversal is made final according to
+    /// the withdrawal dispute Policy. Only the given portion of the disputed amount is charged
+    /// back if the chargeback carries an amount.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn chargeback(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        if self.opening_holds.contains_key(&transaction.tx) {
+            return self.release_opening_hold(transaction);
+        }
+        let (credit, mut state, amount) = self.open_dispute(transaction)?;
+        let mut change = Change {
+            locked: Some(true),
+            ..Change::default()
+        };
+        if credit {
+            change.held = -amount;
+        } else {
+            match self.policy.disputes.withdrawals {
+                WithdrawalDisputes::Disallow | WithdrawalDisputes::ReverseCredit => (),
+                WithdrawalDisputes::HoldAsPendingCredit => {
+                    change.held = -amount;
+                    change.available = amount;
+                }
+            }
+        }
+        state.disputed -= amount;
+        if !state.is_open() {
+            state.opened = None;
+        }
+        change.dispute = Some(state);
+        Ok(change)
+    }
+
+    /// Plan the release of funds an opening balance holds under the transaction's identifier. A
+    /// resolve returns them to available, and a chargeback removes them from the account and
+    /// locks it. Only the given portion is released if the transaction carries an amount.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A resolve or chargeback transaction
+    fn release_opening_hold(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        let remaining = self.opening_holds[&transaction.tx];
+        if remaining.is_zero() {
+            return Err(Rejection::NotDisputed);
+        }
+        let amount = transaction.transaction_type.amount().unwrap_or(remaining);
+        if amount > remaining {
+            return Err(Rejection::ExceedsDisputedAmount);
+        }
+        let mut change = Change {
+            held: -amount,
+            opening_hold: Some((transaction.tx.clone(), remaining - amount)),
+            ..Change::default()
+        };
+        match transaction.transaction_type {
+            TransactionType::Chargeback(_) => change.locked = Some(true),
+            _ => change.available = amount,
+        }
+        Ok(change)
+    }
+
+    /// Plan an unlock transaction. This reinstates an account locked by a chargeback and is only
+    /// allowed if the Policy authorizes unlocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn unlock(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        if self.is_used(&transaction.tx) {
+            return Err(Rejection::DuplicateTransaction);
+        }
+        if !self.policy.admin.allow_unlock {
+            return Err(Rejection::Unauthorized);
+        }
+        if !self.locked {
+            return Err(Rejection::NotLocked);
+        }
+        Ok(Change {
+            locked: Some(false),
+            ..Change::default()
+        })
+    }
+
+    /// Plan a close transaction. Only an account with nothing available, held or owed can be
+    /// closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn close(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        if self.is_used(&transaction.tx) {
+            return Err(Rejection::DuplicateTransaction);
+        }
+        if !self.available.is_zero() || !self.held.is_zero() || !self.total.is_zero() {
+            return Err(Rejection::BalanceNotZero);
+        }
+        Ok(Change {
+            closed: Some(true),
+            ..Change::default()
+        })
+    }
+
+    /// Plan a reopen transaction, allowing a closed account to process transactions again.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn reopen(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        if self.is_used(&transaction.tx) {
+            return Err(Rejection::DuplicateTransaction);
+        }
+        if !self.closed {
+            return Err(Rejection::NotClosed);
+        }
+        Ok(Change {
+            closed: Some(false),
+            ..Change::default()
+        })
+    }
+
+    /// Plan an opening balance, which seeds an account with the available funds, held funds and
+    /// lock state it had in another system. The held funds are recorded as an opening hold under
+    /// the transaction they were held for. Only an account without any history can take one.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn opening(&self, transaction: &Transaction) -> Result<Change, Rejection> {
+        if !self.transactions.transactions.is_empty() {
+            return Err(Rejection::OpeningNotFirst);
+        }
+        match transaction.transaction_type {
+            TransactionType::Opening {
+                available,
+                held,
+                ref held_tx,
+                locked,
+            } => Ok(Change {
+                available,
+                held,
+                opening_hold: held_tx.clone().map(|tx| (tx, held)),
+                locked: Some(locked),
+                ..Change::default()
+            }),
+            _ => Ok(Change::default()),
+        }
+    }
+
+    /// The amount of a disputable transaction and whether it credited the account. Transfers
+    /// credit their destination and debit their source.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction from the account history
+    fn disputed_leg(&self, transaction: &Transaction) -> Option<(Decimal, bool)> {
+        match &transaction.transaction_type {
+            TransactionType::Deposit(amount) => Some((*amount, true)),
+            TransactionType::Withdraw(amount) => Some((*amount, false)),
+            TransactionType::Transfer {
+                destination,
+                amount,
+            } => Some((*amount, *destination == self.client)),
+            _ => None,
+        }
+    }
+
+    /// Looks up the open dispute a resolve or chargeback refers to. Returns whether the disputed
+    /// transaction credited the account, its dispute state and the amount to close, which
+    /// defaults to the whole disputed amount.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A resolve or chargeback transaction
+    fn open_dispute(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<(bool, DisputeState, Decimal), Rejection> {
+        let txn = self
+            .get_transaction(&transaction.tx)
+            .ok_or(Rejection::UnknownTransaction)?;
+        // If the account has no unresolved disputes, there is nothing to resolve or chargeback.
+        if !self.has_unresolved_disputes() || !self.transaction_disputed(txn) {
+            return Err(Rejection::NotDisputed);
+        }
+        let (_, credit) = self.disputed_leg(txn).ok_or(Rejection::NotDisputed)?;
+        let state = self.disputes[&transaction.tx].clone();
+        let amount = transaction
+            .transaction_type
+            .amount()
+            .unwrap_or(state.disputed);
+        if amount > state.disputed {
+            return Err(Rejection::ExceedsDisputedAmount);
+        }
+        Ok((credit, state, amount))
+    }
+
+    /// Recalculates the total from the available and held funds and flags the account if it has
+    /// gone below zero.
+    fn update_total(&mut self) {
+        self.total = self.held + self.available;
+        self.negative = self.available < Decimal::zero() || self.total < Decimal::zero();
+    }
+
+    /// Closes every open dispute that has expired by the provided time, according to the
+    /// dispute expiry Policy. Returns the closing transactions in the order they were applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    pub fn advance_to(&mut self, now: DateTime<Utc>) -> Vec<Transaction> {
+        let mut expired = Vec::new();
+        while let Some((transaction, outcome)) = self.expire_next(now) {
+            match outcome {
+                Ok(()) => expired.push(transaction),
+                Err(rejection) => debug!(
+                    "Client {} rejected expiry of tx {}: {}",
+                    self.client, transaction.tx, rejection
+                ),
+            }
+        }
+        expired
+    }
+
+    /// Closes the earliest open dispute or authorization that has expired by the provided time,
+    /// returning the closing transaction and whether it was applied. Disputes of transfers involve
+    /// a second account and are rejected here; the Ledger closes them with
+    /// `Account::close_expired_pair`.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    pub fn expire_next(
+        &mut self,
+        now: DateTime<Utc>,
+    ) -> Option<(Transaction, Result<(), Rejection>)> {
+        let transaction = self.next_expiry(now)?;
+        let outcome = if self.requires_counterparty(&transaction) {
+            self.stop_expiry(&transaction.tx);
+            Err(Rejection::CounterpartyRequired)
+        } else {
+            self.close_expired(transaction.clone())
+        };
+        Some((transaction, outcome))
+    }
+
+    /// Finds the earliest open dispute or authorization that has expired by the provided time,
+    /// according to the dispute expiry and authorization Policy, and returns the transaction that
+    /// closes it without applying it. Closures are timestamped at the moment of expiry. Expired
+    /// disputes are resolved or charged back, and expired authorizations are voided.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    pub fn next_expiry(&self, now: DateTime<Utc>) -> Option<Transaction> {
+        let dispute = self.policy.disputes.expiry.as_ref().and_then(|expiry| {
+            self.disputes
+                .iter()
+                .filter(|(_, state)| state.is_open())
+                .filter_map(|(tx, state)| {
+                    state
+                        .opened
+                        .map(|opened| (opened + expiry.duration(), tx.clone()))
+                })
+                .filter(|(expires_at, _)| *expires_at <= now)
+                .min()
+                .map(|(expires_at, tx)| {
+                    let transaction_type = match expiry.action {
+                        ExpiryAction::Resolve => TransactionType::Resolve(None),
+                        ExpiryAction::Chargeback => TransactionType::Chargeback(None),
+                    };
+                    (expires_at, tx, transaction_type)
+                })
+        });
+        let authorization = self.policy.authorizations.expiry().and_then(|duration| {
+            self.authorizations
+                .iter()
+                .filter_map(|(tx, authorization)| {
+                    authorization
+                        .opened
+                        .map(|opened| (opened + duration, tx.clone()))
+                })
+                .filter(|(expires_at, _)| *expires_at <= now)
+                .min()
+                .map(|(expires_at, tx)| (expires_at, tx, TransactionType::Void))
+        });
+        let (expires_at, tx, transaction_type) = dispute
+            .into_iter()
+            .chain(authorization)
+            .min_by_key(|(expires_at, tx, _)| (*expires_at, tx.clone()))?;
+        Some(Transaction {
+            transaction_type,
+            tx,
+            client: self.client.clone(),
+            timestamp: Some(expires_at),
+            currency: self.currency.clone(),
+        })
+    }
+
+    /// Applies a closure returned by `Account::next_expiry`. Closures are recorded in the account
+    /// history like any other transaction and apply to locked and closed accounts as well. A
+    /// closure the account rejects stops the expiry clock of its dispute or authorization, so
+    /// that it does not block later expiries.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - The closing transaction.
+    pub fn close_expired(&mut self, transaction: Transaction) -> Result<(), Rejection> {
+        let outcome = self.apply(transaction.clone());
+        if outcome.is_err() {
+            self.stop_expiry(&transaction.tx);
+        }
+        outcome
+    }
+
+    /// Applies the closure of an expired dispute of a transfer to both of its accounts or to
+    /// neither, the way a resolve or chargeback of the transfer would. A closure that either
+    /// account rejects stops the expiry clock of the dispute on both.
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - The account the closure was found on.
+    /// * `second` - The other account involved in the transfer.
+    /// * `transaction` - The closing transaction, as returned by `Account::next_expiry`.
+    pub fn close_expired_pair(
+        first: &mut Account,
+        second: &mut Account,
+        transaction: Transaction,
+    ) -> Result<(), Rejection> {
+        let second_leg = Transaction {
+            client: second.client.clone(),
+            ..transaction.clone()
+        };
+        let changes = first
+            .plan(&transaction)
+            .and_then(|first_change| Ok((first_change, second.plan(&second_leg)?)));
+        match changes {
+            Ok((first_change, second_change)) => {
+                first.commit(first_change, transaction);
+                second.commit(second_change, second_leg);
+                Ok(())
+            }
+            Err(rejection) => {
+                first.stop_expiry(&transaction.tx);
+                second.stop_expiry(&transaction.tx);
+                Err(rejection)
+            }
+        }
+    }
+
+    /// Stops the expiry clock of a dispute or authorization, so that it is no longer closed
+    /// automatically.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - Transaction identifier of the dispute or authorization.
+    fn stop_expiry(&mut self, tx: &Identifier) {
+        if let Some(state) = self.disputes.get_mut(tx) {
+            state.opened = None;
+        }
+        if let Some(authorization) = self.authorizations.get_mut(tx) {
+            authorization.opened = None;
+        }
+    }
+
+    /// Indicated whether a given transaction is disputed. A dispute stays open until it is
+    /// resolved or charged back.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn transaction_disputed(&self, transaction: &Transaction) -> bool {
+        if let TransactionType::Dispute(_) = transaction.transaction_type {
+            false
+        } else {
+            self.disputes
+                .get(&transaction.tx)
+                .is_some_and(DisputeState::is_open)
+        }
+    }
+
+    /// Indicates whether the account has unresolved disputes.
+    fn has_unresolved_disputes(&self) -> bool {
+        self.disputes.values().any(DisputeState::is_open)
+    }
+
+    /// Indicates whether a transaction identifier is already taken by the account history or by
+    /// an opening hold.
+    ///
+    /// # Arguments
+    ///
+    /// * `identifier` - Transaction identifier
+    fn is_used(&self, identifier: &Identifier) -> bool {
+        self.get_transaction(identifier).is_some() || self.opening_holds.contains_key(identifier)
+    }
+
+    /// Returns a transaction by its transaction identifier.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    fn get_transaction(&self, identifier: &Identifier) -> Option<&Transaction> {
+        let mut target_transaction = self
+            .transactions
+            .transactions
+            .iter()
+            .filter(|txn| txn.tx == *identifier);
+        target_transaction.next()
+    }
+}
+
+/// Writes the risk flags of an account as a single column, separated by semicolons.
+fn serialize_flags<S: Serializer>(
+    flags: &BTreeSet<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&flags.iter().join(";"))
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{AdminAction, OpeningBalance};
+    use std::error::Error;
+    use std::str;
+
+    #[test]
+    fn test_from_csv_data() -> Result<(), Box<dyn Error>> {
+        let mut result = Vec::new();
+        let sample_input = std::fs::read("test_data/sample_input.csv").unwrap();
+        let sample_output = std::fs::read_to_string("test_data/sample_output.csv").unwrap();
+
+        Account::accounts_state_from_csv_data(&sample_input, &mut result)?;
+
+        assert_eq!(str::from_utf8(&result).unwrap(), sample_output);
+        Ok(())
     }
 
     #[test]
@@ -329,16 +1585,22 @@ mod tests {
         let mut account = Account::from_transaction_set(TransactionSet {
             transactions: vec![Transaction {
                 transaction_type: TransactionType::Deposit(Decimal::new(5, 0)),
-                tx: 1,
-                client: 4,
+                tx: 1.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
             }],
-            client: 4,
-        });
-        account.dispute(Transaction {
-            transaction_type: TransactionType::Dispute,
-            tx: 1,
-            client: 4,
+            client: 4.into(),
         });
+        account
+            .apply(Transaction {
+                transaction_type: TransactionType::Dispute(None),
+                tx: 1.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            })
+            .unwrap();
 
         assert_eq!(account.total, Decimal::new(50, 1));
         assert_eq!(account.held, Decimal::new(50, 1));
@@ -350,21 +1612,31 @@ mod tests {
         let mut account = Account::from_transaction_set(TransactionSet {
             transactions: vec![Transaction {
                 transaction_type: TransactionType::Deposit(Decimal::new(5, 0)),
-                tx: 1,
-                client: 4,
+                tx: 1.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
             }],
-            client: 4,
-        });
-        account.dispute(Transaction {
-            transaction_type: TransactionType::Dispute,
-            tx: 1,
-            client: 4,
-        });
-        account.resolve(Transaction {
-            transaction_type: TransactionType::Resolve,
-            tx: 1,
-            client: 4,
+            client: 4.into(),
         });
+        account
+            .apply(Transaction {
+                transaction_type: TransactionType::Dispute(None),
+                tx: 1.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            })
+            .unwrap();
+        account
+            .apply(Transaction {
+                transaction_type: TransactionType::Resolve(None),
+                tx: 1.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            })
+            .unwrap();
 
         assert_eq!(account.total, Decimal::new(50, 1));
         assert_eq!(account.held, Decimal::new(00, 1));
@@ -377,35 +1649,32 @@ mod tests {
             transactions: vec![
                 Transaction {
                     transaction_type: TransactionType::Deposit(Decimal::new(5, 0)),
-                    tx: 1,
-                    client: 4,
+                    tx: 1.into(),
+                    client: 4.into(),
+                    timestamp: None,
+                    currency: None,
                 },
                 Transaction {
-                    transaction_type: TransactionType::Dispute,
-                    tx: 1,
-                    client: 4,
+                    transaction_type: TransactionType::Dispute(None),
+                    tx: 1.into(),
+                    client: 4.into(),
+                    timestamp: None,
+                    currency: None,
                 },
                 Transaction {
                     transaction_type: TransactionType::Deposit(Decimal::new(3, 0)),
-                    tx: 2,
-                    client: 4,
+                    tx: 2.into(),
+                    client: 4.into(),
+                    timestamp: None,
+                    currency: None,
                 },
             ],
-            client: 4,
+            client: 4.into(),
         });
 
-        assert_eq!(
-            account.transaction_disputed(&account.transactions.transactions[0]),
-            true
-        );
-        assert_eq!(
-            account.transaction_disputed(&account.transactions.transactions[1]),
-            false
-        );
-        assert_eq!(
-            account.transaction_disputed(&account.transactions.transactions[2]),
-            false
-        );
+        assert!(account.transaction_disputed(&account.transactions.transactions[0]));
+        assert!(!account.transaction_disputed(&account.transactions.transactions[1]));
+        assert!(!account.transaction_disputed(&account.transactions.transactions[2]));
     }
 
     #[test]
@@ -414,33 +1683,1166 @@ mod tests {
             transactions: vec![
                 Transaction {
                     transaction_type: TransactionType::Deposit(Decimal::new(5, 0)),
-                    tx: 1,
-                    client: 4,
+                    tx: 1.into(),
+                    client: 4.into(),
+                    timestamp: None,
+                    currency: None,
                 },
                 Transaction {
-                    transaction_type: TransactionType::Dispute,
-                    tx: 1,
-                    client: 4,
+                    transaction_type: TransactionType::Dispute(None),
+                    tx: 1.into(),
+                    client: 4.into(),
+                    timestamp: None,
+                    currency: None,
                 },
                 Transaction {
                     transaction_type: TransactionType::Deposit(Decimal::new(3, 0)),
-                    tx: 2,
-                    client: 4,
+                    tx: 2.into(),
+                    client: 4.into(),
+                    timestamp: None,
+                    currency: None,
                 },
             ],
-            client: 4,
+            client: 4.into(),
         });
 
         let undisputed_account = Account::from_transaction_set(TransactionSet {
             transactions: vec![Transaction {
                 transaction_type: TransactionType::Deposit(Decimal::new(5, 0)),
-                tx: 1,
-                client: 4,
+                tx: 1.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
             }],
-            client: 4,
+            client: 4.into(),
         });
 
-        assert_eq!(disputed_account.has_unresolved_disputes(), true);
-        assert_eq!(undisputed_account.has_unresolved_disputes(), false);
+        assert!(disputed_account.has_unresolved_disputes());
+        assert!(!undisputed_account.has_unresolved_disputes());
+    }
+
+    #[test]
+    fn test_chargeback() {
+        let mut account = Account::from_transaction_set(TransactionSet {
+            transactions: vec![
+                Transaction {
+                    transaction_type: TransactionType::Deposit(Decimal::new(5, 0)),
+                    tx: 1.into(),
+                    client: 4.into(),
+                    timestamp: None,
+                    currency: None,
+                },
+                Transaction {
+                    transaction_type: TransactionType::Deposit(Decimal::new(3, 0)),
+                    tx: 2.into(),
+                    client: 4.into(),
+                    timestamp: None,
+                    currency: None,
+                },
+                Transaction {
+                    transaction_type: TransactionType::Dispute(None),
+                    tx: 1.into(),
+                    client: 4.into(),
+                    timestamp: None,
+                    currency: None,
+                },
+            ],
+            client: 4.into(),
+        });
+        account
+            .apply(Transaction {
+                transaction_type: TransactionType::Chargeback(None),
+                tx: 1.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            })
+            .unwrap();
+
+        assert_eq!(account.total, Decimal::new(30, 1));
+        assert_eq!(account.held, Decimal::new(00, 1));
+        assert_eq!(account.available, Decimal::new(30, 1));
+        assert!(account.locked);
+        assert_eq!(
+            account.resolve_new_transaction(Transaction {
+                transaction_type: TransactionType::Deposit(Decimal::new(1, 0)),
+                tx: 3.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            }),
+            Err(Rejection::AccountLocked)
+        );
+    }
+
+    #[test]
+    fn test_rejections() {
+        let mut account = Account::from_transaction_set(TransactionSet {
+            transactions: vec![Transaction {
+                transaction_type: TransactionType::Deposit(Decimal::new(5, 0)),
+                tx: 1.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            }],
+            client: 4.into(),
+        });
+
+        assert_eq!(
+            account.resolve_new_transaction(Transaction {
+                transaction_type: TransactionType::Withdraw(Decimal::new(6, 0)),
+                tx: 2.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            }),
+            Err(Rejection::InsufficientFunds)
+        );
+        assert_eq!(
+            account.resolve_new_transaction(Transaction {
+                transaction_type: TransactionType::Deposit(Decimal::new(1, 0)),
+                tx: 1.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            }),
+            Err(Rejection::DuplicateTransaction)
+        );
+        assert_eq!(
+            account.resolve_new_transaction(Transaction {
+                transaction_type: TransactionType::Chargeback(None),
+                tx: 9.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            }),
+            Err(Rejection::UnknownTransaction)
+        );
+        assert_eq!(
+            account.resolve_new_transaction(Transaction {
+                transaction_type: TransactionType::Resolve(None),
+                tx: 1.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            }),
+            Err(Rejection::NotDisputed)
+        );
+        assert_eq!(
+            account.resolve_new_transaction(Transaction {
+                transaction_type: TransactionType::Deposit(Decimal::new(1, 0)),
+                tx: 3.into(),
+                client: 5.into(),
+                timestamp: None,
+                currency: None,
+            }),
+            Err(Rejection::WrongClient)
+        );
+        assert_eq!(account.total, Decimal::new(50, 1));
+        assert!(!account.locked);
+    }
+
+    fn timestamped(transaction_type: TransactionType, tx: u64, timestamp: &str) -> Transaction {
+        Transaction {
+            transaction_type,
+            tx: tx.into(),
+            client: 4.into(),
+            timestamp: Transaction::parse_timestamp(timestamp).ok(),
+            currency: None,
+        }
+    }
+
+    #[test]
+    fn test_dispute_window() {
+        let policy = Policy::from_yaml(b"disputes:\n  window_days: 10\n").unwrap();
+        let account = Account::from_transaction_set_with_policy(
+            TransactionSet {
+                transactions: vec![
+                    timestamped(
+                        TransactionType::Deposit(Decimal::new(5, 0)),
+                        1,
+                        "2021-01-01T00:00:00Z",
+                    ),
+                    timestamped(
+                        TransactionType::Deposit(Decimal::new(3, 0)),
+                        2,
+                        "2021-01-08T00:00:00Z",
+                    ),
+                    timestamped(TransactionType::Dispute(None), 1, "2021-01-12T00:00:00Z"),
+                    timestamped(TransactionType::Dispute(None), 2, "2021-01-12T00:00:00Z"),
+                ],
+                client: 4.into(),
+            },
+            Arc::new(policy),
+        );
+
+        assert_eq!(account.held, Decimal::new(30, 1));
+        assert!(!account.transaction_disputed(&account.transactions.transactions[0]));
+        assert!(account.transaction_disputed(&account.transactions.transactions[1]));
+    }
+
+    #[test]
+    fn test_dispute_expiry() {
+        let policy =
+            Policy::from_yaml(b"disputes:\n  expiry:\n    after_days: 30\n    action: resolve\n")
+                .unwrap();
+        let mut account = Account::from_transaction_set_with_policy(
+            TransactionSet {
+                transactions: vec![
+                    timestamped(
+                        TransactionType::Deposit(Decimal::new(5, 0)),
+                        1,
+                        "2021-01-01T00:00:00Z",
+                    ),
+                    timestamped(TransactionType::Dispute(None), 1, "2021-01-02T00:00:00Z"),
+                ],
+                client: 4.into(),
+            },
+            Arc::new(policy),
+        );
+
+        assert!(account
+            .advance_to(Transaction::parse_timestamp("2021-01-31T00:00:00Z").unwrap())
+            .is_empty());
+        assert_eq!(account.held, Decimal::new(50, 1));
+
+        let expired =
+            account.advance_to(Transaction::parse_timestamp("2021-02-01T00:00:00Z").unwrap());
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].transaction_type, TransactionType::Resolve(None));
+        assert_eq!(account.held, Decimal::new(00, 1));
+        assert_eq!(account.available, Decimal::new(50, 1));
+        assert!(!account.locked);
+    }
+
+    #[test]
+    fn test_rejected_expiry() {
+        let policy =
+            Policy::from_yaml(b"disputes:\n  expiry:\n    after_days: 30\n    action: resolve\n")
+                .unwrap();
+        let mut account = Account::from_transaction_set_with_policy(
+            TransactionSet {
+                transactions: vec![
+                    timestamped(
+                        TransactionType::Deposit(Decimal::new(5, 0)),
+                        1,
+                        "2021-01-01T00:00:00Z",
+                    ),
+                    timestamped(TransactionType::Dispute(None), 1, "2021-01-03T00:00:00Z"),
+                ],
+                client: 4.into(),
+            },
+            Arc::new(policy),
+        );
+        // A dispute the account can no longer close expires first.
+        account.disputes.insert(
+            9.into(),
+            DisputeState {
+                disputed: Decimal::new(1, 0),
+                disputable: Decimal::new(00, 1),
+                opened: Transaction::parse_timestamp("2021-01-02T00:00:00Z").ok(),
+            },
+        );
+        let now = Transaction::parse_timestamp("2021-03-01T00:00:00Z").unwrap();
+
+        let (first, outcome) = account.expire_next(now).unwrap();
+        assert_eq!(first.tx, 9.into());
+        assert_eq!(outcome, Err(Rejection::UnknownTransaction));
+
+        let (second, outcome) = account.expire_next(now).unwrap();
+        assert_eq!(second.tx, 1.into());
+        assert_eq!(outcome, Ok(()));
+        assert!(account.expire_next(now).is_none());
+        assert_eq!(account.held, Decimal::new(00, 1));
+        assert_eq!(account.available, Decimal::new(50, 1));
+    }
+
+    fn withdrawal_dispute(withdrawals: WithdrawalDisputes, closure: TransactionType) -> Account {
+        let mut policy = Policy::default();
+        policy.disputes.withdrawals = withdrawals;
+        let mut account = Account::from_transaction_set_with_policy(
+            TransactionSet {
+                transactions: vec![
+                    Transaction {
+                        transaction_type: TransactionType::Deposit(Decimal::new(10, 0)),
+                        tx: 1.into(),
+                        client: 4.into(),
+                        timestamp: None,
+                        currency: None,
+                    },
+                    Transaction {
+                        transaction_type: TransactionType::Withdraw(Decimal::new(4, 0)),
+                        tx: 2.into(),
+                        client: 4.into(),
+                        timestamp: None,
+                        currency: None,
+                    },
+                ],
+                client: 4.into(),
+            },
+            Arc::new(policy),
+        );
+        let _ = account.resolve_new_transaction(Transaction {
+            transaction_type: TransactionType::Dispute(None),
+            tx: 2.into(),
+            client: 4.into(),
+            timestamp: None,
+            currency: None,
+        });
+        let _ = account.resolve_new_transaction(Transaction {
+            transaction_type: closure,
+            tx: 2.into(),
+            client: 4.into(),
+            timestamp: None,
+            currency: None,
+        });
+        account
+    }
+
+    #[test]
+    fn test_withdrawal_disputes_disallowed() {
+        let account =
+            withdrawal_dispute(WithdrawalDisputes::Disallow, TransactionType::Resolve(None));
+
+        assert!(!account.has_unresolved_disputes());
+        assert_eq!(account.available, Decimal::new(6, 0));
+        assert_eq!(account.held, Decimal::new(0, 0));
+        assert_eq!(account.total, Decimal::new(6, 0));
+    }
+
+    #[test]
+    fn test_withdrawal_disputes_reverse_credit() {
+        let mut policy = Policy::default();
+        policy.disputes.withdrawals = WithdrawalDisputes::ReverseCredit;
+        let disputed = Account::from_transaction_set_with_policy(
+            TransactionSet {
+                transactions: vec![
+                    Transaction {
+                        transaction_type: TransactionType::Withdraw(Decimal::new(4, 0)),
+                        tx: 2.into(),
+                        client: 4.into(),
+                        timestamp: None,
+                        currency: None,
+                    },
+                    Transaction {
+                        transaction_type: TransactionType::Dispute(None),
+                        tx: 2.into(),
+                        client: 4.into(),
+                        timestamp: None,
+                        currency: None,
+                    },
+                ],
+                client: 4.into(),
+            },
+            Arc::new(policy),
+        );
+        assert_eq!(disputed.available, Decimal::new(0, 0));
+
+        let resolved = withdrawal_dispute(
+            WithdrawalDisputes::ReverseCredit,
+            TransactionType::Resolve(None),
+        );
+        assert_eq!(resolved.available, Decimal::new(6, 0));
+        assert_eq!(resolved.held, Decimal::new(0, 0));
+        assert_eq!(resolved.total, Decimal::new(6, 0));
+
+        let charged_back = withdrawal_dispute(
+            WithdrawalDisputes::ReverseCredit,
+            TransactionType::Chargeback(None),
+        );
+        assert_eq!(charged_back.available, Decimal::new(10, 0));
+        assert_eq!(charged_back.held, Decimal::new(0, 0));
+        assert_eq!(charged_back.total, Decimal::new(10, 0));
+        assert!(charged_back.locked);
+    }
+
+    #[test]
+    fn test_withdrawal_disputes_hold_as_pending_credit() {
+        assert_eq!(
+            Policy::default().disputes.withdrawals,
+            WithdrawalDisputes::HoldAsPendingCredit
+        );
+        let open = withdrawal_dispute(
+            WithdrawalDisputes::HoldAsPendingCredit,
+            TransactionType::Dispute(None),
+        );
+        assert_eq!(open.available, Decimal::new(6, 0));
+        assert_eq!(open.held, Decimal::new(4, 0));
+        assert_eq!(open.total, Decimal::new(10, 0));
+
+        let resolved = withdrawal_dispute(
+            WithdrawalDisputes::HoldAsPendingCredit,
+            TransactionType::Resolve(None),
+        );
+        assert_eq!(resolved.available, Decimal::new(6, 0));
+        assert_eq!(resolved.held, Decimal::new(0, 0));
+        assert_eq!(resolved.total, Decimal::new(6, 0));
+
+        let charged_back = withdrawal_dispute(
+            WithdrawalDisputes::HoldAsPendingCredit,
+            TransactionType::Chargeback(None),
+        );
+        assert_eq!(charged_back.available, Decimal::new(10, 0));
+        assert_eq!(charged_back.held, Decimal::new(0, 0));
+        assert_eq!(charged_back.total, Decimal::new(10, 0));
+        assert!(charged_back.locked);
+    }
+
+    #[test]
+    fn test_overdraft_limit() {
+        let mut policy = Policy::default();
+        policy.overdraft.limit = Decimal::new(5, 0);
+        policy
+            .overdraft
+            .clients
+            .insert(5.into(), Decimal::new(1, 0));
+        let policy = Arc::new(policy);
+        let withdraw = |client: u64, tx: u64, amount: i64| Transaction {
+            transaction_type: TransactionType::Withdraw(Decimal::new(amount, 0)),
+            tx: tx.into(),
+            client: client.into(),
+            timestamp: None,
+            currency: None,
+        };
+
+        let mut account = Account::with_policy(4.into(), policy.clone());
+        assert_eq!(account.resolve_new_transaction(withdraw(4, 1, 3)), Ok(()));
+        assert_eq!(
+            account.resolve_new_transaction(withdraw(4, 2, 3)),
+            Err(Rejection::InsufficientFunds)
+        );
+        assert_eq!(account.available, Decimal::new(-3, 0));
+        assert!(account.balance().negative);
+
+        let mut account = Account::with_policy(5.into(), policy);
+        assert_eq!(
+            account.resolve_new_transaction(withdraw(5, 1, 3)),
+            Err(Rejection::InsufficientFunds)
+        );
+        assert_eq!(account.resolve_new_transaction(withdraw(5, 2, 1)), Ok(()));
+        assert!(account.balance().negative);
+    }
+
+    #[test]
+    fn test_negative_after_dispute() {
+        let transactions = vec![
+            Transaction {
+                transaction_type: TransactionType::Deposit(Decimal::new(5, 0)),
+                tx: 1.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            },
+            Transaction {
+                transaction_type: TransactionType::Withdraw(Decimal::new(4, 0)),
+                tx: 2.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            },
+        ];
+        let dispute = Transaction {
+            transaction_type: TransactionType::Dispute(None),
+            tx: 1.into(),
+            client: 4.into(),
+            timestamp: None,
+            currency: None,
+        };
+
+        let mut account = Account::from_transaction_set(TransactionSet {
+            transactions: transactions.clone(),
+            client: 4.into(),
+        });
+        assert_eq!(account.resolve_new_transaction(dispute.clone()), Ok(()));
+        assert_eq!(account.available, Decimal::new(-4, 0));
+        assert!(account.negative);
+
+        let mut policy = Policy::default();
+        policy.overdraft.allow_negative_after_dispute = false;
+        let mut account = Account::from_transaction_set_with_policy(
+            TransactionSet {
+                transactions,
+                client: 4.into(),
+            },
+            Arc::new(policy),
+        );
+        assert_eq!(
+            account.resolve_new_transaction(dispute),
+            Err(Rejection::InsufficientFunds)
+        );
+        assert_eq!(account.available, Decimal::new(1, 0));
+        assert!(!account.negative);
+    }
+
+    #[test]
+    fn test_partial_disputes() {
+        let mut account = Account::new(4.into());
+        let mut apply = |transaction_type: TransactionType| {
+            let outcome = account.resolve_new_transaction(Transaction {
+                transaction_type,
+                tx: 1.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            });
+            (outcome, account.balance())
+        };
+
+        apply(TransactionType::Deposit(Decimal::new(10, 0)))
+            .0
+            .unwrap();
+        let (outcome, balance) = apply(TransactionType::Dispute(Some(Decimal::new(4, 0))));
+        assert_eq!(outcome, Ok(()));
+        assert_eq!(balance.available, Decimal::new(6, 0));
+        assert_eq!(balance.held, Decimal::new(4, 0));
+        assert_eq!(
+            apply(TransactionType::Dispute(Some(Decimal::new(7, 0)))).0,
+            Err(Rejection::ExceedsDisputableAmount)
+        );
+        let (outcome, balance) = apply(TransactionType::Dispute(None));
+        assert_eq!(outcome, Ok(()));
+        assert_eq!(balance.held, Decimal::new(10, 0));
+        assert_eq!(
+            apply(TransactionType::Dispute(None)).0,
+            Err(Rejection::AlreadyDisputed)
+        );
+        let (outcome, balance) = apply(TransactionType::Resolve(Some(Decimal::new(3, 0))));
+        assert_eq!(outcome, Ok(()));
+        assert_eq!(balance.available, Decimal::new(3, 0));
+        assert_eq!(balance.held, Decimal::new(7, 0));
+        assert_eq!(
+            apply(TransactionType::Chargeback(Some(Decimal::new(8, 0)))).0,
+            Err(Rejection::ExceedsDisputedAmount)
+        );
+        let (outcome, balance) = apply(TransactionType::Chargeback(Some(Decimal::new(2, 0))));
+        assert_eq!(outcome, Ok(()));
+        assert_eq!(balance.held, Decimal::new(5, 0));
+        assert_eq!(balance.total, Decimal::new(8, 0));
+        assert!(balance.locked);
+
+        let state = account.dispute_state(&1.into()).unwrap();
+        assert_eq!(state.disputed, Decimal::new(5, 0));
+        assert_eq!(state.disputable, Decimal::new(3, 0));
+    }
+
+    #[test]
+    fn test_partial_dispute_from_csv() {
+        let input = Input::from_csv_data(
+            b"type,client,tx,amount\ndeposit,1,1,10.0\ndispute,1,1,2.5\ndispute,1,1,\nresolve,1,1,0\n",
+            &InputOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(input.transactions.len(), 3);
+        let account = Account::from_transaction_set(input.transaction_sets().remove(0));
+
+        assert_eq!(account.available, Decimal::new(0, 0));
+        assert_eq!(account.held, Decimal::new(100, 1));
+    }
+
+    #[test]
+    fn test_negative_amounts_from_csv() {
+        let input = Input::from_csv_data(
+            b"type,client,tx,amount,reference\n\
+              deposit,1,1,-5.0,\n\
+              authorize,1,2,-1.0,\n\
+              deposit,1,3,10.0,\n\
+              adjustment,1,4,-2.0,ticket-1\n",
+            &InputOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(input.transactions.len(), 2);
+        let account = Account::from_transaction_set(input.transaction_sets().remove(0));
+
+        assert_eq!(account.available, Decimal::new(80, 1));
+        assert_eq!(account.held, Decimal::new(0, 0));
+    }
+
+    #[test]
+    fn test_admin_transactions() {
+        let data = b"type,client,tx,amount,operator,reason
+deposit,1,1,5.0,,
+dispute,1,1,,,
+chargeback,1,1,,,
+unlock,1,2,,,
+unlock,1,3,,alice,reviewed
+close,1,4,,bob,
+deposit,1,5,1.0,,
+reopen,1,6,,bob,customer request
+deposit,1,7,1.0,,
+";
+        let input = Input::from_csv_data(data, &InputOptions::default()).unwrap();
+        let transactions = input.transaction_sets().remove(0).transactions;
+        assert_eq!(transactions.len(), 8);
+
+        let outcomes = |policy: Policy| {
+            Replay::new(
+                Account::with_policy(1.into(), Arc::new(policy)),
+                transactions.clone(),
+            )
+            .map(|step| step.outcome)
+            .collect::<Vec<_>>()
+        };
+        let denied = outcomes(Policy::default());
+        assert_eq!(denied[3], Err(Rejection::Unauthorized));
+        assert_eq!(denied[4], Err(Rejection::AccountLocked));
+
+        let mut policy = Policy::default();
+        policy.admin.allow_unlock = true;
+        assert_eq!(
+            outcomes(policy),
+            vec![
+                Ok(()),
+                Ok(()),
+                Ok(()),
+                Ok(()),
+                Ok(()),
+                Err(Rejection::AccountClosed),
+                Ok(()),
+                Ok(()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_status_columns() {
+        let data = b"type,client,tx,amount,operator,reason
+deposit,1,1,5.0,,
+withdraw,1,2,5.0,,
+close,1,3,,bob,
+deposit,2,4,3.0,,
+withdraw,2,5,2.0,,
+dispute,2,4,,,
+";
+        let render = |status: bool| {
+            let mut result = Vec::new();
+            Account::accounts_state_from_input(
+                Input::from_csv_data(data, &InputOptions::default()).unwrap(),
+                &Arc::default(),
+                &OutputOptions {
+                    status,
+                    ..OutputOptions::default()
+                },
+                &mut result,
+            )
+            .unwrap();
+            String::from_utf8(result).unwrap()
+        };
+
+        assert_eq!(
+            render(false),
+            "client,available,held,total,locked\n\
+             1,0.0,0.0,0.0,false\n\
+             2,-2.0,3.0,1.0,false\n\n"
+        );
+        assert_eq!(
+            render(true),
+            "client,available,held,total,locked,closed,negative,flags\n\
+             1,0.0,0.0,0.0,false,true,false,\n\
+             2,-2.0,3.0,1.0,false,false,true,\n\n"
+        );
+    }
+
+    #[test]
+    fn test_close_requires_zero_balance() {
+        let mut account = Account::new(1.into());
+        let admin = |tx: u64, transaction_type: fn(AdminAction) -> TransactionType| Transaction {
+            transaction_type: transaction_type(AdminAction {
+                operator: String::from("alice"),
+                reason: None,
+            }),
+            tx: tx.into(),
+            client: 1.into(),
+            timestamp: None,
+            currency: None,
+        };
+        assert_eq!(
+            account.resolve_new_transaction(admin(1, TransactionType::Reopen)),
+            Err(Rejection::NotClosed)
+        );
+        account
+            .resolve_new_transaction(Transaction {
+                transaction_type: TransactionType::Deposit(Decimal::new(1, 0)),
+                tx: 2.into(),
+                client: 1.into(),
+                timestamp: None,
+                currency: None,
+            })
+            .unwrap();
+        assert_eq!(
+            account.resolve_new_transaction(admin(3, TransactionType::Close)),
+            Err(Rejection::BalanceNotZero)
+        );
+        assert!(!account.balance().closed);
+    }
+
+    #[test]
+    fn test_fees_interest_and_adjustments() {
+        let input = Input::from_csv_data(
+            &std::fs::read("test_data/fees_input.csv").unwrap(),
+            &InputOptions::default(),
+        )
+        .unwrap();
+        // The adjustment without a reference is skipped.
+        assert_eq!(input.transactions.len(), 6);
+        let transactions = input.transaction_sets().remove(0).transactions;
+        let outcomes: Vec<Result<(), Rejection>> =
+            Replay::new(Account::new(1.into()), transactions.clone())
+                .map(|step| step.outcome)
+                .collect();
+        assert_eq!(outcomes[4], Err(Rejection::NotDisputable));
+        assert_eq!(outcomes[5], Err(Rejection::InsufficientFunds));
+
+        let account = Account::from_transaction_set(TransactionSet {
+            transactions: transactions.clone(),
+            client: 1.into(),
+        });
+        assert_eq!(account.available, Decimal::new(70, 1));
+        let breakdown = account.breakdown();
+        assert_eq!(breakdown.deposits, Decimal::new(100, 1));
+        assert_eq!(breakdown.fees, Decimal::new(25, 1));
+        assert_eq!(breakdown.interest, Decimal::new(5, 1));
+        assert_eq!(breakdown.adjustments, Decimal::new(-10, 1));
+
+        let mut policy = Policy::default();
+        policy.fees.allow_negative = true;
+        let account = Account::from_transaction_set_with_policy(
+            TransactionSet {
+                transactions,
+                client: 1.into(),
+            },
+            Arc::new(policy),
+        );
+        assert_eq!(account.available, Decimal::new(-130, 1));
+        assert!(account.negative);
+    }
+
+    #[test]
+    fn test_authorizations() {
+        let mut account = Account::new(4.into());
+        let mut apply = |transaction_type: TransactionType, tx: u64| {
+            let outcome = account.resolve_new_transaction(Transaction {
+                transaction_type,
+                tx: tx.into(),
+                client: 4.into(),
+                timestamp: None,
+                currency: None,
+            });
+            (outcome, account.balance())
+        };
+
+        apply(TransactionType::Deposit(Decimal::new(10, 0)), 1)
+            .0
+            .unwrap();
+        assert_eq!(
+            apply(TransactionType::Authorize(Decimal::new(11, 0)), 2).0,
+            Err(Rejection::InsufficientFunds)
+        );
+        let (outcome, balance) = apply(TransactionType::Authorize(Decimal::new(6, 0)), 3);
+        assert_eq!(outcome, Ok(()));
+        assert_eq!(balance.available, Decimal::new(4, 0));
+        assert_eq!(balance.held, Decimal::new(6, 0));
+        assert_eq!(balance.authorized, Decimal::new(6, 0));
+
+        assert_eq!(
+            apply(TransactionType::Capture(Some(Decimal::new(7, 0))), 3).0,
+            Err(Rejection::ExceedsAuthorizedAmount)
+        );
+        let (outcome, balance) = apply(TransactionType::Capture(Some(Decimal::new(2, 0))), 3);
+        assert_eq!(outcome, Ok(()));
+        assert_eq!(balance.held, Decimal::new(4, 0));
+        assert_eq!(balance.total, Decimal::new(8, 0));
+
+        // Disputes are accounted separately from the authorization.
+        apply(TransactionType::Dispute(None), 1).0.unwrap();
+        apply(TransactionType::Resolve(None), 1).0.unwrap();
+        let (outcome, balance) = apply(TransactionType::Void, 3);
+        assert_eq!(outcome, Ok(()));
+        assert_eq!(balance.available, Decimal::new(8, 0));
+        assert_eq!(balance.held, Decimal::new(0, 0));
+        assert_eq!(balance.authorized, Decimal::new(0, 0));
+        assert_eq!(
+            apply(TransactionType::Capture(None), 3).0,
+            Err(Rejection::NotAuthorized)
+        );
+        assert_eq!(account.breakdown().withdrawals, Decimal::new(2, 0));
+    }
+
+    #[test]
+    fn test_authorization_expiry() {
+        let policy = Policy::from_yaml(b"authorizations:\n  expire_after_days: 7\n").unwrap();
+        let mut account = Account::from_transaction_set_with_policy(
+            TransactionSet {
+                transactions: vec![
+                    timestamped(
+                        TransactionType::Deposit(Decimal::new(10, 0)),
+                        1,
+                        "2021-01-01T00:00:00Z",
+                    ),
+                    timestamped(
+                        TransactionType::Authorize(Decimal::new(4, 0)),
+                        2,
+                        "2021-01-02T00:00:00Z",
+                    ),
+                ],
+                client: 4.into(),
+            },
+            Arc::new(policy),
+        );
+        let expired =
+            account.advance_to(Transaction::parse_timestamp("2021-01-10T00:00:00Z").unwrap());
+
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].transaction_type, TransactionType::Void);
+        assert_eq!(account.available, Decimal::new(10, 0));
+        assert_eq!(account.held, Decimal::new(0, 0));
+    }
+
+    #[test]
+    fn test_wide_identifiers() {
+        let input = Input::from_csv_data(
+            b"type,client,tx,amount\n\
+              deposit,18446744073709551615,9007199254740993,10.0\n\
+              deposit,3f2b8c1e-6a4d-4e2b-9c1a-7d5e8f0a1b2c,4294967296,5.0\n\
+              dispute,18446744073709551615,9007199254740993,\n\
+              deposit,0042,1,1.0\n",
+            &InputOptions::default(),
+        )
+        .unwrap();
+        let clients: Vec<Identifier> = input
+            .transactions
+            .iter()
+            .map(|transaction| transaction.client.clone())
+            .collect();
+        assert_eq!(
+            clients,
+            vec![
+                Identifier::Numeric(u64::MAX),
+                Identifier::Text(String::from("3f2b8c1e-6a4d-4e2b-9c1a-7d5e8f0a1b2c")),
+                Identifier::Numeric(u64::MAX),
+                Identifier::Text(String::from("0042")),
+            ]
+        );
+
+        let account = Account::from_transaction_set(input.transaction_sets().remove(0));
+        assert_eq!(*account.client(), Identifier::Numeric(u64::MAX));
+        assert!(account
+            .dispute_state(&9007199254740993.into())
+            .is_some_and(DisputeState::is_open));
+
+        let policy = Policy::from_yaml(
+            b"overdraft:\n  clients:\n    7: 1.0\n    3f2b8c1e-6a4d-4e2b-9c1a-7d5e8f0a1b2c: 2.0\n",
+        )
+        .unwrap();
+        assert_eq!(policy.overdraft.floor(&7.into()), Decimal::new(-1, 0));
+        assert_eq!(
+            policy
+                .overdraft
+                .floor(&"3f2b8c1e-6a4d-4e2b-9c1a-7d5e8f0a1b2c".into()),
+            Decimal::new(-2, 0)
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let policy = Policy::from_yaml(
+            b"limits:\n  default:\n    max_withdrawal: 50\n    daily_withdrawal: 60\n    \
+              rolling_withdrawal:\n      days: 3\n      amount: 80\n    \
+              max_transactions:\n      count: 3\n      window_seconds: 60\n    \
+              max_balance: 200\n  clients:\n    5:\n      max_withdrawal: 500\n",
+        )
+        .unwrap();
+        let mut account = Account::with_policy(4.into(), Arc::new(policy));
+        let mut apply = |transaction_type: TransactionType, tx: u64, timestamp: &str| {
+            account.resolve_new_transaction(timestamped(transaction_type, tx, timestamp))
+        };
+        let deposit = |amount: i64| TransactionType::Deposit(Decimal::new(amount, 0));
+        let withdraw = |amount: i64| TransactionType::Withdraw(Decimal::new(amount, 0));
+
+        assert_eq!(apply(deposit(150), 1, "2021-01-01T00:00:00Z"), Ok(()));
+        assert_eq!(
+            apply(deposit(60), 2, "2021-01-01T01:00:00Z"),
+            Err(Rejection::ExceedsBalanceLimit)
+        );
+        assert_eq!(
+            apply(withdraw(51), 3, "2021-01-01T02:00:00Z"),
+            Err(Rejection::ExceedsWithdrawalLimit)
+        );
+        assert_eq!(apply(withdraw(40), 4, "2021-01-01T03:00:00Z"), Ok(()));
+        assert_eq!(
+            apply(withdraw(30), 5, "2021-01-01T04:00:00Z"),
+            Err(Rejection::ExceedsDailyWithdrawalLimit)
+        );
+        // The daily limit resets at midnight, but the rolling limit still counts yesterday.
+        assert_eq!(apply(withdraw(30), 6, "2021-01-02T00:00:00Z"), Ok(()));
+        assert_eq!(
+            apply(withdraw(20), 7, "2021-01-03T00:00:00Z"),
+            Err(Rejection::ExceedsRollingWithdrawalLimit)
+        );
+        assert_eq!(apply(withdraw(20), 8, "2021-01-04T03:00:01Z"), Ok(()));
+
+        // Three deposits and withdrawals within a minute use up the transaction limit.
+        assert_eq!(apply(deposit(1), 9, "2021-01-04T03:00:30Z"), Ok(()));
+        assert_eq!(apply(deposit(1), 10, "2021-01-04T03:00:40Z"), Ok(()));
+        assert_eq!(
+            apply(deposit(1), 11, "2021-01-04T03:00:50Z"),
+            Err(Rejection::TooManyTransactions)
+        );
+        assert_eq!(apply(deposit(1), 12, "2021-01-04T03:01:05Z"), Ok(()));
+
+        // Clients with their own limits do not use the defaults.
+        let policy = Policy::from_yaml(b"limits:\n  default:\n    max_withdrawal: 50\n  clients:\n    5:\n      max_withdrawal: 500\n").unwrap();
+        let mut account = Account::with_policy(5.into(), Arc::new(policy));
+        let outcomes: Vec<Result<(), Rejection>> = vec![
+            TransactionType::Deposit(Decimal::new(100, 0)),
+            TransactionType::Withdraw(Decimal::new(80, 0)),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(tx, transaction_type)| {
+            account.resolve_new_transaction(Transaction {
+                transaction_type,
+                tx: (tx as u64).into(),
+                client: 5.into(),
+                timestamp: None,
+                currency: None,
+            })
+        })
+        .collect();
+        assert_eq!(outcomes, vec![Ok(()), Ok(())]);
+    }
+
+    #[test]
+    fn test_limits_apply_to_every_flow() {
+        let policy = Arc::new(
+            Policy::from_yaml(
+                b"limits:\n  default:\n    max_withdrawal: 50\n    daily_withdrawal: 60\n    \
+                  max_balance: 100\n  clients:\n    5:\n      max_balance: 100\n",
+            )
+            .unwrap(),
+        );
+        let mut account = Account::with_policy(4.into(), policy.clone());
+        let mut other = Account::with_policy(5.into(), policy);
+        let amount = |value: i64| Decimal::new(value, 0);
+        assert_eq!(
+            account.resolve_new_transaction(timestamped(
+                TransactionType::Deposit(amount(90)),
+                1,
+                "2021-01-01T00:00:00Z"
+            )),
+            Ok(())
+        );
+        assert_eq!(
+            other.resolve_new_transaction(Transaction {
+                client: 5.into(),
+                ..timestamped(
+                    TransactionType::Deposit(amount(90)),
+                    2,
+                    "2021-01-01T00:00:00Z"
+                )
+            }),
+            Ok(())
+        );
+
+        // Every outflow is held to the single withdrawal limit.
+        let adjustment = |value: i64| TransactionType::Adjustment {
+            amount: amount(value),
+            reference: String::from("correction"),
+        };
+        for (tx, transaction_type) in [
+            (3, TransactionType::Fee(amount(51))),
+            (4, TransactionType::Authorize(amount(51))),
+            (5, adjustment(-51)),
+        ] {
+            assert_eq!(
+                account.resolve_new_transaction(timestamped(
+                    transaction_type,
+                    tx,
+                    "2021-01-01T01:00:00Z"
+                )),
+                Err(Rejection::ExceedsWithdrawalLimit)
+            );
+        }
+        let transfer = |tx: u64, client: u64, destination: u64, value: i64| Transaction {
+            client: client.into(),
+            ..timestamped(
+                TransactionType::Transfer {
+                    destination: destination.into(),
+                    amount: amount(value),
+                },
+                tx,
+                "2021-01-01T02:00:00Z",
+            )
+        };
+        assert_eq!(
+            Account::resolve_paired_transaction(&mut account, &mut other, transfer(6, 4, 5, 51)),
+            Err(Rejection::ExceedsWithdrawalLimit)
+        );
+
+        // Outflows of every kind add up towards the daily limit, and a capture only finalizes
+        // what its authorization already counted.
+        assert_eq!(
+            account.resolve_new_transaction(timestamped(
+                TransactionType::Authorize(amount(30)),
+                7,
+                "2021-01-01T03:00:00Z"
+            )),
+            Ok(())
+        );
+        assert_eq!(
+            account.resolve_new_transaction(timestamped(
+                TransactionType::Capture(None),
+                7,
+                "2021-01-01T04:00:00Z"
+            )),
+            Ok(())
+        );
+        assert_eq!(
+            account.resolve_new_transaction(timestamped(
+                TransactionType::Fee(amount(20)),
+                8,
+                "2021-01-01T05:00:00Z"
+            )),
+            Ok(())
+        );
+        assert_eq!(
+            account.resolve_new_transaction(timestamped(
+                TransactionType::Withdraw(amount(20)),
+                9,
+                "2021-01-01T06:00:00Z"
+            )),
+            Err(Rejection::ExceedsDailyWithdrawalLimit)
+        );
+
+        // Every inflow is held to the balance limit.
+        assert_eq!(
+            Account::resolve_paired_transaction(&mut other, &mut account, transfer(10, 5, 4, 61)),
+            Err(Rejection::ExceedsBalanceLimit)
+        );
+        for (tx, transaction_type) in [
+            (11, TransactionType::Interest(amount(61))),
+            (12, adjustment(61)),
+        ] {
+            assert_eq!(
+                account.resolve_new_transaction(timestamped(
+                    transaction_type,
+                    tx,
+                    "2021-01-01T07:00:00Z"
+                )),
+                Err(Rejection::ExceedsBalanceLimit)
+            );
+        }
+        assert_eq!(account.total, amount(40));
+        assert_eq!(other.total, amount(90));
+    }
+
+    #[test]
+    fn test_opening_balances() -> Result<(), Box<dyn Error>> {
+        let balances = OpeningBalance::from_csv_data(
+            b"client,available,held,locked\n1,10.0,0.0,false\n2,1.0,0.0,true\n",
+        )?;
+        // A transaction named `opening` in the input is distinct from the opening balance.
+        let input = Input::from_csv_data(
+            b"type,client,tx,amount\n\
+              deposit,1,1,5.0\n\
+              deposit,1,opening,1.0\n\
+              dispute,1,opening,\n\
+              withdraw,1,2,12.0\n\
+              deposit,2,3,1.0\n",
+            &InputOptions::default(),
+        )?
+        .with_opening_balances(&balances);
+
+        let mut sets = input.clone().transaction_sets();
+        let outcomes: Vec<Result<(), Rejection>> =
+            Replay::new(Account::new(1.into()), sets[0].transactions.clone())
+                .map(|step| step.outcome)
+                .collect();
+        assert_eq!(outcomes, vec![Ok(()), Ok(()), Ok(()), Ok(()), Ok(())]);
+        let account = Account::from_transaction_set(sets.remove(0));
+        assert_eq!(account.transactions()[0].transaction_type.name(), "opening");
+        assert_eq!(account.available, Decimal::new(30, 1));
+        assert_eq!(account.held, Decimal::new(10, 1));
+        let account = Account::from_transaction_set(sets.remove(0));
+        assert!(account.locked);
+        assert_eq!(account.total, Decimal::new(10, 1));
+
+        let mut result = Vec::new();
+        Account::accounts_state_from_input(
+            input,
+            &Arc::default(),
+            &OutputOptions::default(),
+            &mut result,
+        )?;
+        assert_eq!(
+            str::from_utf8(&result)?,
+            "client,available,held,total,locked\n\
+             1,3.0,1.0,4.0,false\n\
+             2,1.0,0.0,1.0,true\n\n"
+        );
+
+        // An opening balance only seeds an account without any history.
+        let mut account = Account::new(1.into());
+        account.resolve_new_transaction(Transaction {
+            transaction_type: TransactionType::Deposit(Decimal::new(5, 0)),
+            tx: 1.into(),
+            client: 1.into(),
+            timestamp: None,
+            currency: None,
+        })?;
+        assert_eq!(
+            account.resolve_new_transaction(balances[0].transaction()),
+            Err(Rejection::OpeningNotFirst)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_opening_hold() -> Result<(), Box<dyn Error>> {
+        let balances = OpeningBalance::from_csv_data(
+            b"client,available,held,locked,held_tx\n\
+              1,10.0,3.0,false,legacy-1\n\
+              2,0.0,2.0,false,legacy-2\n",
+        )?;
+        let input = Input::from_csv_data(
+            b"type,client,tx,amount\n\
+              dispute,1,legacy-1,\n\
+              deposit,1,legacy-1,1.0\n\
+              resolve,1,legacy-1,1.0\n\
+              resolve,1,legacy-1,5.0\n\
+              resolve,1,legacy-1,\n\
+              resolve,1,legacy-1,\n\
+              chargeback,2,legacy-2,\n",
+            &InputOptions::default(),
+        )?
+        .with_opening_balances(&balances);
+
+        let mut sets = input.transaction_sets();
+        let outcomes: Vec<Result<(), Rejection>> =
+            Replay::new(Account::new(1.into()), sets[0].transactions.clone())
+                .map(|step| step.outcome)
+                .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                Ok(()),
+                Err(Rejection::NotDisputable),
+                Err(Rejection::DuplicateTransaction),
+                Ok(()),
+                Err(Rejection::ExceedsDisputedAmount),
+                Ok(()),
+                Err(Rejection::NotDisputed),
+            ]
+        );
+        let account = Account::from_transaction_set(sets.remove(0));
+        assert_eq!(account.available, Decimal::new(130, 1));
+        assert_eq!(account.held, Decimal::new(00, 1));
+        assert_eq!(account.total, Decimal::new(130, 1));
+
+        // A chargeback removes the held funds and locks the account.
+        let account = Account::from_transaction_set(sets.remove(0));
+        assert_eq!(account.held, Decimal::new(00, 1));
+        assert_eq!(account.total, Decimal::new(00, 1));
+        assert!(account.locked);
+        Ok(())
     }
 }
@@ -1,8 +1,62 @@
+mod admin_action;
+mod as_of;
+mod authorization;
+mod balance;
+mod breakdown;
+mod currency;
+mod dispute_state;
+mod fx;
+mod generator;
+mod identifier;
+mod input;
+mod invariant;
+mod ledger;
 mod main;
+mod opening;
+mod ordering;
+mod output;
+mod parse_error;
+mod policy;
+mod reconciliation;
+mod rejection;
+mod replay;
+mod risk;
+mod statement;
 mod transaction;
 mod transaction_set;
 mod transaction_type;
+mod validation;
+pub use admin_action::AdminAction;
+pub use as_of::AsOf;
+pub use authorization::Authorization;
+pub use balance::Balance;
+pub use breakdown::Breakdown;
+pub use currency::Currency;
+pub use dispute_state::DisputeState;
+pub use fx::{Conversion, RateTable};
+pub use generator::{Generator, GeneratorOptions};
+pub use identifier::Identifier;
+pub use input::{Input, InputOptions};
+pub use invariant::InvariantViolation;
+pub use ledger::Ledger;
 pub use main::Account;
+pub use opening::OpeningBalance;
+pub use ordering::ReorderBuffer;
+pub use output::{AccountRow, OutputOptions, Report};
+pub use parse_error::{ParseError, ParseErrorKind};
+pub use policy::{
+    AdminPolicy, AuthorizationPolicy, BuiltinRule, DisputeExpiry, DisputePolicy, ExpiryAction,
+    FeePolicy, LimitPolicy, Limits, OverdraftPolicy, Policy, RiskPolicy, RiskRuleConfig,
+    RollingLimit, TransactionLimit, WithdrawalDisputes,
+};
+pub use reconciliation::{Discrepancy, ExpectedBalances};
+pub use rejection::{RejectedTransaction, Rejection};
+pub use replay::{Replay, Step};
+pub use risk::{
+    DepositThenWithdrawal, RepeatedDisputes, RiskAction, RiskEvent, RiskRule, StaleDispute,
+};
+pub use statement::StatementRow;
 pub use transaction::Transaction;
 pub use transaction_set::TransactionSet;
 pub use transaction_type::TransactionType;
+pub use validation::{Issue, IssueKind, Validation};
@@ -0,0 +1,217 @@
+use super::{Currency, Identifier, Transaction, TransactionType};
+use anyhow::{bail, Context, Error, Result};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Transaction identifier every opening balance is recorded under. Input cannot produce an empty
+/// identifier, so an opening balance never collides with a real transaction.
+const OPENING_TX: &str = "";
+
+/// The state an account had in another system before its transactions are replayed here.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpeningBalance {
+    /// Client identifier of the account.
+    pub client: Identifier,
+    /// Currency of the account, if known.
+    #[serde(default)]
+    pub currency: Option<Currency>,
+    /// The funds available at the start of the history.
+    pub available: Decimal,
+    /// The funds held at the start of the history.
+    pub held: Decimal,
+    /// Identifier of the transaction the held funds were held under, which a later resolve or
+    /// chargeback names to release them. Required if any funds are held.
+    #[serde(default)]
+    pub held_tx: Option<Identifier>,
+    /// Whether the account starts locked.
+    pub locked: bool,
+}
+
+/// A single row of an opening balances CSV file. Amounts are read as text so that they keep
+/// their exact value and scale.
+#[derive(Debug, Deserialize)]
+struct OpeningRow {
+    client: String,
+    #[serde(default)]
+    currency: Option<String>,
+    available: String,
+    held: String,
+    locked: bool,
+    #[serde(default)]
+    held_tx: Option<String>,
+}
+
+impl OpeningBalance {
+    /// Reads opening balances from CSV data with `client`, `available`, `held` and `locked`
+    /// columns, and optional `currency` and `held_tx` columns.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Reference to a Vec<u8> of CSV data.
+    pub fn from_csv_data(data: &[u8]) -> Result<Vec<OpeningBalance>, Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(data);
+        let mut balances = Vec::new();
+        for row in reader.deserialize() {
+            let row: OpeningRow = row.context("Failed to parse opening balance row.")?;
+            let amount = |field: &str, value: &str| {
+                Decimal::from_str(value)
+                    .with_context(|| format!("Failed to parse opening balance {}.", field))
+            };
+            let held_tx = match row.held_tx.as_deref().filter(|tx| !tx.is_empty()) {
+                Some(tx) => Some(
+                    tx.parse::<Identifier>()
+                        .map_err(Error::msg)
+                        .context("Failed to parse opening balance held_tx.")?,
+                ),
+                None => None,
+            };
+            let currency = match row.currency.as_deref().filter(|code| !code.is_empty()) {
+                Some(code) => Some(
+                    code.parse::<Currency>()
+                        .map_err(Error::msg)
+                        .context("Failed to parse opening balance currency.")?,
+                ),
+                None => None,
+            };
+            balances.push(
+                OpeningBalance {
+                    client: row
+                        .client
+                        .parse::<Identifier>()
+                        .map_err(Error::msg)
+                        .context("Failed to parse opening balance client.")?,
+                    currency,
+                    available: amount("available", &row.available)?,
+                    held: amount("held", &row.held)?,
+                    held_tx,
+                    locked: row.locked,
+                }
+                .checked()?,
+            );
+        }
+        Ok(balances)
+    }
+
+    /// Reads opening balances from a JSON array of objects with `client`, `available`, `held`
+    /// and `locked` fields, and optional `currency` and `held_tx` fields.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Reference to a Vec<u8> of JSON data.
+    pub fn from_json_data(data: &[u8]) -> Result<Vec<OpeningBalance>, Error> {
+        let balances: Vec<OpeningBalance> =
+            serde_json::from_slice(data).context("Failed to parse opening balances.")?;
+        balances.into_iter().map(OpeningBalance::checked).collect()
+    }
+
+    /// The transaction that seeds the account with this balance. It must be the first
+    /// transaction applied to the account.
+    pub fn transaction(&self) -> Transaction {
+        Transaction {
+            transaction_type: TransactionType::Opening {
+                available: self.available,
+                held: self.held,
+                held_tx: self.held_tx.clone(),
+                locked: self.locked,
+            },
+            tx: Identifier::from(OPENING_TX),
+            client: self.client.clone(),
+            timestamp: None,
+            currency: self.currency.clone(),
+        }
+    }
+
+    /// Checks that the held funds of the opening balance are not negative, and that any held
+    /// funds name the transaction that releases them.
+    fn checked(self) -> Result<OpeningBalance, Error> {
+        if self.held < Decimal::zero() {
+            bail!(
+                "Opening balance of client {} holds a negative amount.",
+                self.client
+            );
+        }
+        if self.held > Decimal::zero() && self.held_tx.is_none() {
+            bail!(
+                "Opening balance of client {} holds funds without a held_tx to release them.",
+                self.client
+            );
+        }
+        Ok(self)
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opening_balances_from_csv() {
+        let balances = OpeningBalance::from_csv_data(
+            b"client,currency,available,held,locked\n\
+              1,,10.50,0.0,false\n\
+              007,usd,-1.0,0,true\n",
+        )
+        .unwrap();
+        assert_eq!(balances.len(), 2);
+        assert_eq!(balances[0].available.to_string(), "10.50");
+        assert_eq!(balances[1].client, "007".into());
+        assert_eq!(balances[1].currency, Some("USD".parse().unwrap()));
+        assert!(balances[1].locked);
+
+        assert!(OpeningBalance::from_csv_data(b"client,available,held\n1,1.0,0\n").is_err());
+        assert!(
+            OpeningBalance::from_csv_data(b"client,available,held,locked\n1,1.0,-1.0,false\n")
+                .is_err()
+        );
+        assert!(
+            OpeningBalance::from_csv_data(b"client,available,held,locked\n1,1.0,2.0,false\n")
+                .is_err()
+        );
+        let balances = OpeningBalance::from_csv_data(
+            b"client,available,held,locked,held_tx\n1,1.0,2.0,false,legacy-1\n2,1.0,0.0,false,\n",
+        )
+        .unwrap();
+        assert_eq!(balances[0].held, Decimal::new(20, 1));
+        assert_eq!(balances[0].held_tx, Some("legacy-1".parse().unwrap()));
+        assert_eq!(balances[1].held_tx, None);
+    }
+
+    #[test]
+    fn test_opening_balances_from_json() {
+        let balances = OpeningBalance::from_json_data(
+            br#"[
+                {"client": 1, "available": "10.50", "held": "0.0", "locked": false},
+                {"client": "007", "currency": "eur", "available": 3, "held": 0, "locked": true}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            balances[0].transaction().transaction_type,
+            TransactionType::Opening {
+                available: Decimal::new(1050, 2),
+                held: Decimal::new(00, 1),
+                held_tx: None,
+                locked: false,
+            }
+        );
+        assert_eq!(balances[0].transaction().transaction_type.amount(), None);
+        assert_eq!(balances[1].client, "007".into());
+        assert_eq!(balances[1].currency, Some("EUR".parse().unwrap()));
+        assert_eq!(balances[1].transaction().tx, Identifier::from(OPENING_TX));
+
+        assert!(OpeningBalance::from_json_data(br#"[{"client": 1, "available": 1}]"#).is_err());
+        assert!(OpeningBalance::from_json_data(
+            br#"[{"client": 1, "available": 1, "held": 1, "locked": false}]"#
+        )
+        .is_err());
+        let balances = OpeningBalance::from_json_data(
+            br#"[{"client": 1, "available": 1, "held": 1, "locked": false, "held_tx": 9}]"#,
+        )
+        .unwrap();
+        assert_eq!(balances[0].held_tx, Some(9.into()));
+    }
+}
@@ -0,0 +1,180 @@
+use super::Transaction;
+use chrono::{DateTime, Duration, Utc};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+/// Reorders a stream of transactions by timestamp. Transactions are held back until no earlier
+/// transaction can still arrive within the lateness window, then released in timestamp order.
+/// A transaction whose timestamp is older than the window allows is late and is handed back to
+/// the caller instead.
+///
+/// Transactions without a timestamp are ordered as if they occurred at the latest timestamp seen
+/// so far, which keeps them in stream order relative to their neighbours.
+#[derive(Debug)]
+pub struct ReorderBuffer {
+    /// How far behind the latest seen timestamp a transaction may arrive and still be reordered.
+    lateness: Duration,
+    /// The latest timestamp seen so far.
+    latest: Option<DateTime<Utc>>,
+    /// Transactions waiting to be released, earliest first.
+    pending: BinaryHeap<Reverse<Pending>>,
+    /// Arrival counter used to keep transactions with equal timestamps in stream order.
+    sequence: u64,
+}
+
+/// A transaction waiting in the ReorderBuffer along with its ordering key.
+#[derive(Debug)]
+struct Pending {
+    timestamp: Option<DateTime<Utc>>,
+    sequence: u64,
+    transaction: Transaction,
+}
+
+impl Pending {
+    fn key(&self) -> (Option<DateTime<Utc>>, u64) {
+        (self.timestamp, self.sequence)
+    }
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl ReorderBuffer {
+    /// Creates an empty buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `lateness` - How far behind the latest seen timestamp a transaction may arrive and still
+    ///   be applied in order.
+    pub fn new(lateness: Duration) -> ReorderBuffer {
+        ReorderBuffer {
+            lateness,
+            latest: None,
+            pending: BinaryHeap::new(),
+            sequence: 0,
+        }
+    }
+
+    /// The point before which no further transactions are accepted.
+    fn watermark(&self) -> Option<DateTime<Utc>> {
+        self.latest.map(|latest| latest - self.lateness)
+    }
+
+    /// Adds a transaction to the buffer. A transaction older than the lateness window allows is
+    /// returned as the error.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A transaction of any TransactionType
+    pub fn push(&mut self, transaction: Transaction) -> Result<(), Box<Transaction>> {
+        let timestamp = match transaction.timestamp {
+            Some(timestamp) => {
+                if self
+                    .watermark()
+                    .is_some_and(|watermark| timestamp < watermark)
+                {
+                    return Err(Box::new(transaction));
+                }
+                self.latest = Some(
+                    self.latest
+                        .map_or(timestamp, |latest| latest.max(timestamp)),
+                );
+                Some(timestamp)
+            }
+            None => self.latest,
+        };
+        self.sequence += 1;
+        self.pending.push(Reverse(Pending {
+            timestamp,
+            sequence: self.sequence,
+            transaction,
+        }));
+        Ok(())
+    }
+
+    /// Releases the next transaction that can no longer be preceded by a late arrival.
+    pub fn pop_ready(&mut self) -> Option<Transaction> {
+        let watermark = self.watermark()?;
+        match self.pending.peek() {
+            Some(Reverse(next)) if next.timestamp.is_none_or(|ts| ts <= watermark) => {
+                self.pending.pop().map(|Reverse(next)| next.transaction)
+            }
+            _ => None,
+        }
+    }
+
+    /// Releases every remaining transaction in timestamp order.
+    pub fn drain(self) -> impl Iterator<Item = Transaction> {
+        self.pending
+            .into_sorted_vec()
+            .into_iter()
+            .rev()
+            .map(|Reverse(next)| next.transaction)
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{Identifier, TransactionType};
+    use rust_decimal::prelude::*;
+
+    fn deposit(tx: u64, seconds: i64) -> Transaction {
+        Transaction {
+            transaction_type: TransactionType::Deposit(Decimal::new(1, 0)),
+            tx: tx.into(),
+            client: 1.into(),
+            timestamp: Transaction::parse_timestamp(&seconds.to_string()).ok(),
+            currency: None,
+        }
+    }
+
+    #[test]
+    fn test_reorders_within_window() {
+        let mut buffer = ReorderBuffer::new(Duration::seconds(10));
+        let mut released = Vec::new();
+        for transaction in [
+            deposit(1, 100),
+            deposit(3, 108),
+            deposit(2, 104),
+            deposit(4, 120),
+        ] {
+            buffer.push(transaction).unwrap();
+            while let Some(transaction) = buffer.pop_ready() {
+                released.push(transaction.tx);
+            }
+        }
+        assert_eq!(released, [1, 2, 3].map(Identifier::from));
+        released.extend(buffer.drain().map(|transaction| transaction.tx));
+        assert_eq!(released, [1, 2, 3, 4].map(Identifier::from));
+    }
+
+    #[test]
+    fn test_rejects_late_arrivals() {
+        let mut buffer = ReorderBuffer::new(Duration::seconds(10));
+        buffer.push(deposit(1, 100)).unwrap();
+        buffer.push(deposit(2, 120)).unwrap();
+
+        assert_eq!(buffer.push(deposit(3, 105)).unwrap_err().tx, 3.into());
+        assert!(buffer.push(deposit(4, 110)).is_ok());
+    }
+}
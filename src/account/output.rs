@@ -0,0 +1,83 @@
+use super::{Account, Conversion, Currency, Identifier, RejectedTransaction, RiskEvent};
+use itertools::Itertools;
+use rust_decimal::prelude::*;
+use serde::Serialize;
+
+/// Options controlling how rendered accounts are written out.
+#[derive(Debug, Clone, Default)]
+pub struct OutputOptions {
+    /// Whether to add columns totalling the applied transactions by category.
+    pub breakdown: bool,
+    /// Whether to add columns describing the status of each account: whether it is closed,
+    /// whether it is below zero and which risk rules have flagged it.
+    pub status: bool,
+    /// When present, each client's balances in every currency are converted and combined into a
+    /// single row.
+    pub conversion: Option<Conversion>,
+    /// Whether to check balance invariants after every applied transaction in release builds.
+    /// Checks always run in debug builds, and any violation fails the run.
+    pub paranoid: bool,
+}
+
+/// What happened while rendering accounts, besides the accounts themselves.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// Every firing of a risk rule, in order.
+    pub risk_events: Vec<RiskEvent>,
+    /// Every transaction that was rejected, in order.
+    pub rejections: Vec<RejectedTransaction>,
+}
+
+/// A single row of the rendered accounts. Columns that are left out of the output are `None` in
+/// every row, so that all rows have the same columns.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountRow {
+    /// Client identifier.
+    pub client: Identifier,
+    /// Currency of the account, written only when some account in the output has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<Option<Currency>>,
+    /// The funds that are available.
+    pub available: Decimal,
+    /// The funds held for disputes and authorizations.
+    pub held: Decimal,
+    /// The funds in all states.
+    pub total: Decimal,
+    /// Whether the account is locked.
+    pub locked: bool,
+    /// Whether the account is closed, written only with the status columns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closed: Option<bool>,
+    /// Whether the available funds or the total are below zero, written only with the status
+    /// columns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub negative: Option<bool>,
+    /// Names of the risk rules that have flagged the account, separated by semicolons, written
+    /// only with the status columns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flags: Option<String>,
+}
+
+impl AccountRow {
+    /// Builds the output row of a rendered account.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - The rendered account.
+    /// * `currency` - Whether to write the currency column.
+    /// * `status` - Whether to write the status columns.
+    pub fn new(account: &Account, currency: bool, status: bool) -> AccountRow {
+        let balance = account.balance();
+        AccountRow {
+            client: account.client().clone(),
+            currency: Some(account.currency().cloned()).filter(|_| currency),
+            available: balance.available,
+            held: balance.held,
+            total: balance.total,
+            locked: balance.locked,
+            closed: Some(balance.closed).filter(|_| status),
+            negative: Some(balance.negative).filter(|_| status),
+            flags: Some(account.flags().iter().join(";")).filter(|_| status),
+        }
+    }
+}
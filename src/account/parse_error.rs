@@ -0,0 +1,52 @@
+use std::fmt;
+
+/// Kinds of problems that stop a CSV row from being parsed into a Transaction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseErrorKind {
+    /// The row has no transaction type, or names one that does not exist.
+    UnknownType,
+    /// The row's amount is missing, malformed or negative.
+    BadAmount,
+    /// Another field of the row is missing or cannot be parsed.
+    InvalidRow,
+}
+
+/// A CSV row that could not be parsed into a Transaction, with the kind of problem and a message
+/// describing it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// The kind of problem with the row.
+    pub kind: ParseErrorKind,
+    /// Describes the problem.
+    pub message: String,
+}
+
+impl ParseError {
+    /// Creates a ParseError of the given kind.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of problem with the row
+    /// * `message` - Describes the problem
+    pub fn new(kind: ParseErrorKind, message: impl Into<String>) -> ParseError {
+        ParseError {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+/// Field parsers that only describe the problem report an invalid row.
+impl From<String> for ParseError {
+    fn from(message: String) -> ParseError {
+        ParseError::new(ParseErrorKind::InvalidRow, message)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
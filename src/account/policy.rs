@@ -0,0 +1,342 @@
+use super::{
+    DepositThenWithdrawal, Identifier, RepeatedDisputes, RiskAction, RiskRule, StaleDispute,
+};
+use anyhow::{Context, Result};
+use chrono::Duration;
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Configurable rules applied by every Account. The defaults set no limits, time windows or risk
+/// rules. They differ from the engine's behaviour before policies existed in one respect: a
+/// dispute of a withdrawal used to move the amount out of held into available, which drove held
+/// below zero, and now holds it as a pending credit instead (see `WithdrawalDisputes`). Policies
+/// are usually loaded from YAML:
+///
+/// ```yaml
+/// disputes:
+///   withdrawals: hold_as_pending_credit
+///   window_days: 120
+///   expiry:
+///     after_days: 30
+///     action: resolve
+/// overdraft:
+///   limit: 0
+///   clients:
+///     7: 250.0
+///   allow_negative_after_dispute: true
+/// admin:
+///   allow_unlock: false
+/// fees:
+///   allow_negative: false
+/// authorizations:
+///   expire_after_days: 7
+/// risk:
+///   rules:
+///     - rule: repeated_disputes
+///       count: 3
+///       window_hours: 24
+///       action: lock
+/// limits:
+///   default:
+///     max_withdrawal: 1000
+///     daily_withdrawal: 2500
+///     rolling_withdrawal:
+///       days: 7
+///       amount: 10000
+///     max_transactions:
+///       count: 20
+///       window_seconds: 3600
+///     max_balance: 100000
+///   clients:
+///     7:
+///       max_withdrawal: 50
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    /// Rules for disputes.
+    pub disputes: DisputePolicy,
+    /// How far accounts may go below zero.
+    pub overdraft: OverdraftPolicy,
+    /// Which administrative transactions are authorized.
+    pub admin: AdminPolicy,
+    /// How fees are charged.
+    pub fees: FeePolicy,
+    /// How long authorization holds last.
+    pub authorizations: AuthorizationPolicy,
+    /// Limits on the funds each client moves.
+    pub limits: LimitPolicy,
+    /// Heuristics that flag or lock suspicious accounts.
+    pub risk: RiskPolicy,
+}
+
+/// Rules for disputes. Time limits only apply to transactions that carry timestamps.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisputePolicy {
+    /// How disputes of withdrawals affect the account.
+    pub withdrawals: WithdrawalDisputes,
+    /// Disputes opened more than this many days after the disputed transaction are rejected.
+    pub window_days: Option<u32>,
+    /// Open disputes that are neither resolved nor charged back in time are closed automatically.
+    pub expiry: Option<DisputeExpiry>,
+}
+
+/// How disputes of withdrawals affect the account. Deposit disputes always move the disputed
+/// amount from available into held.
+///
+/// | Policy                   | dispute                | resolve                | chargeback (locks)     |
+/// |--------------------------|------------------------|------------------------|------------------------|
+/// | `disallow`               | rejected               | -                      | -                      |
+/// | `reverse_credit`         | available and total +a | available and total -a | no change              |
+/// | `hold_as_pending_credit` | held and total +a      | held and total -a      | held -a, available +a  |
+///
+/// `reverse_credit` makes the disputed amount spendable immediately, so resolving the dispute
+/// can drive available negative. `hold_as_pending_credit` keeps it out of reach until the
+/// dispute is decided. It is the default, replacing the earlier behaviour of a withdrawal dispute
+/// (available +a, held -a, total unchanged), which left held negative and has no equivalent here.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WithdrawalDisputes {
+    /// Withdrawals cannot be disputed.
+    Disallow,
+    /// A dispute credits the withdrawn amount back to available straight away. Resolving the
+    /// dispute debits it again; a chargeback keeps the credit.
+    ReverseCredit,
+    /// A dispute credits the withdrawn amount to held as a pending credit. Resolving the dispute
+    /// removes it; a chargeback releases it to available.
+    #[default]
+    HoldAsPendingCredit,
+}
+
+/// Automatic closure of disputes that stay open too long.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DisputeExpiry {
+    /// How many days a dispute may stay open.
+    pub after_days: u32,
+    /// How an expired dispute is closed.
+    pub action: ExpiryAction,
+}
+
+/// How an expired dispute is closed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryAction {
+    /// Release the held funds back to the account.
+    Resolve,
+    /// Remove the held funds and lock the account.
+    Chargeback,
+}
+
+/// How far the available funds of an account may go below zero.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OverdraftPolicy {
+    /// How far withdrawals may take available funds below zero for clients without their own
+    /// limit.
+    pub limit: Decimal,
+    /// Overdraft limits for individual clients, replacing the default limit.
+    pub clients: BTreeMap<Identifier, Decimal>,
+    /// Whether a dispute may take available funds below the overdraft limit. Disputes that would
+    /// do so are rejected if not.
+    pub allow_negative_after_dispute: bool,
+}
+
+/// Which administrative transactions are authorized. Closing and reopening accounts is always
+/// allowed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminPolicy {
+    /// Whether locked accounts may be unlocked. Unlocks are rejected if not.
+    pub allow_unlock: bool,
+}
+
+/// How fees are charged.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeePolicy {
+    /// Whether fees are charged even when they take available funds below the overdraft limit.
+    /// Such fees are rejected as insufficient funds if not.
+    pub allow_negative: bool,
+}
+
+/// How long authorization holds last. Limits only apply to transactions that carry timestamps.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthorizationPolicy {
+    /// Authorizations neither captured nor voided this many days after they were made are voided
+    /// automatically.
+    pub expire_after_days: Option<u32>,
+}
+
+/// Limits on the funds each client moves.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitPolicy {
+    /// Limits for clients without their own.
+    pub default: Limits,
+    /// Limits for individual clients, replacing the default limits.
+    pub clients: BTreeMap<Identifier, Limits>,
+}
+
+/// Limits checked before a transaction that moves funds is applied. Withdrawals, outgoing
+/// transfers, fees, authorizations and negative adjustments are outflows; deposits, incoming
+/// transfers, interest and positive adjustments are inflows. Every limit is optional, and limits
+/// over a window of time only apply to transactions that carry timestamps.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// The largest amount a single outflow may take.
+    pub max_withdrawal: Option<Decimal>,
+    /// The most that may flow out in a UTC calendar day.
+    pub daily_withdrawal: Option<Decimal>,
+    /// The most that may flow out over a rolling window of days.
+    pub rolling_withdrawal: Option<RollingLimit>,
+    /// The most inflows and outflows that may be made over a rolling window of seconds.
+    pub max_transactions: Option<TransactionLimit>,
+    /// The largest total an inflow may take the account to.
+    pub max_balance: Option<Decimal>,
+}
+
+/// A limit on the amount that flows out over a rolling window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RollingLimit {
+    /// Length of the window in days.
+    pub days: u32,
+    /// The most that may flow out within the window.
+    pub amount: Decimal,
+}
+
+/// A limit on the number of transactions made over a rolling window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransactionLimit {
+    /// The most inflows and outflows that may be made within the window.
+    pub count: u32,
+    /// Length of the window in seconds.
+    pub window_seconds: u32,
+}
+
+/// Heuristics that flag or lock suspicious accounts, evaluated on every applied transaction.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskPolicy {
+    /// The rules to evaluate, in order.
+    pub rules: Vec<RiskRuleConfig>,
+}
+
+/// A built-in risk rule and what to do when it fires.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RiskRuleConfig {
+    /// The rule and its settings.
+    #[serde(flatten)]
+    pub rule: BuiltinRule,
+    /// What happens to the account when the rule fires. Defaults to flagging it.
+    #[serde(default)]
+    pub action: RiskAction,
+}
+
+/// The risk rules that can be configured in a Policy.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum BuiltinRule {
+    /// Several disputes in a short span.
+    RepeatedDisputes(RepeatedDisputes),
+    /// A deposit immediately followed by a withdrawal of all of it.
+    DepositThenWithdrawal(DepositThenWithdrawal),
+    /// A dispute of an old transaction.
+    StaleDispute(StaleDispute),
+}
+
+impl Default for OverdraftPolicy {
+    fn default() -> Self {
+        OverdraftPolicy {
+            limit: Decimal::new(00, 1),
+            clients: BTreeMap::new(),
+            allow_negative_after_dispute: true,
+        }
+    }
+}
+
+impl Policy {
+    /// Parses a Policy from YAML. Missing sections take their default values.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Reference to a Vec<u8> buffer containing YAML data
+    pub fn from_yaml(data: &[u8]) -> Result<Policy> {
+        serde_yaml::from_slice(data).context("Failed to parse policy YAML.")
+    }
+}
+
+impl DisputePolicy {
+    /// How long after a transaction it may still be disputed, if limited.
+    pub fn window(&self) -> Option<Duration> {
+        self.window_days.map(|days| Duration::days(days.into()))
+    }
+}
+
+impl AuthorizationPolicy {
+    /// How long an authorization may stay open, if limited.
+    pub fn expiry(&self) -> Option<Duration> {
+        self.expire_after_days
+            .map(|days| Duration::days(days.into()))
+    }
+}
+
+impl BuiltinRule {
+    /// The configured rule, ready to be evaluated.
+    pub fn rule(&self) -> Box<dyn RiskRule> {
+        match self {
+            BuiltinRule::RepeatedDisputes(rule) => Box::new(rule.clone()),
+            BuiltinRule::DepositThenWithdrawal(rule) => Box::new(rule.clone()),
+            BuiltinRule::StaleDispute(rule) => Box::new(rule.clone()),
+        }
+    }
+}
+
+impl LimitPolicy {
+    /// The limits that apply to the provided client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client identifier
+    pub fn for_client(&self, client: &Identifier) -> &Limits {
+        self.clients.get(client).unwrap_or(&self.default)
+    }
+}
+
+impl RollingLimit {
+    /// Length of the window.
+    pub fn window(&self) -> Duration {
+        Duration::days(self.days.into())
+    }
+}
+
+impl TransactionLimit {
+    /// Length of the window.
+    pub fn window(&self) -> Duration {
+        Duration::seconds(self.window_seconds.into())
+    }
+}
+
+impl DisputeExpiry {
+    /// How long a dispute may stay open.
+    pub fn duration(&self) -> Duration {
+        Duration::days(self.after_days.into())
+    }
+}
+
+impl OverdraftPolicy {
+    /// The lowest available amount the provided client may reach through withdrawals.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client identifier
+    pub fn floor(&self, client: &Identifier) -> Decimal {
+        -self.clients.get(client).copied().unwrap_or(self.limit)
+    }
+}
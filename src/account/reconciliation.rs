@@ -0,0 +1,279 @@
+use super::{Balance, Currency, Identifier};
+use anyhow::{Context, Error, Result};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Closing balances of every account as reported by an outside party such as the bank.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExpectedBalances {
+    /// The expected balance of each account, by client and currency.
+    balances: BTreeMap<(Identifier, Option<Currency>), Balance>,
+}
+
+/// A single row of an expected balances file. Other columns, such as those written alongside the
+/// balances in account output, are ignored. Amounts are read as text so that they keep their
+/// exact value and scale.
+#[derive(Debug, Deserialize)]
+struct ExpectedRow {
+    client: String,
+    #[serde(default)]
+    currency: Option<String>,
+    available: String,
+    held: String,
+    total: String,
+    locked: bool,
+}
+
+/// A difference between an expected and an actual account balance.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Discrepancy {
+    /// Client identifier of the account.
+    pub client: Identifier,
+    /// Currency of the account, if known.
+    pub currency: Option<Currency>,
+    /// The field that differs: `available`, `held`, `total` or `locked`, or `account` when only
+    /// one side has the account.
+    pub field: &'static str,
+    /// The expected value.
+    pub expected: String,
+    /// The value rendered from the transactions.
+    pub actual: String,
+    /// The actual amount minus the expected amount, for the amount fields.
+    pub difference: Option<Decimal>,
+}
+
+impl ExpectedBalances {
+    /// Reads expected balances from CSV data in the shape of the account output. The `client`,
+    /// `available`, `held`, `total` and `locked` columns are required, and `currency` is optional.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Reference to a Vec<u8> of CSV data.
+    pub fn from_csv_data(data: &[u8]) -> Result<ExpectedBalances, Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(data);
+        let mut expected = ExpectedBalances::default();
+        for row in reader.deserialize() {
+            let row: ExpectedRow = row.context("Failed to parse expected balance row.")?;
+            let client = row
+                .client
+                .parse::<Identifier>()
+                .map_err(Error::msg)
+                .context("Failed to parse expected balance client.")?;
+            let currency = match row.currency.as_deref() {
+                None | Some("") => None,
+                Some(code) => Some(
+                    code.parse::<Currency>()
+                        .map_err(Error::msg)
+                        .context("Failed to parse expected balance currency.")?,
+                ),
+            };
+            let amount = |field: &str, value: &str| {
+                Decimal::from_str(value)
+                    .with_context(|| format!("Failed to parse expected balance {}.", field))
+            };
+            let available = amount("available", &row.available)?;
+            let total = amount("total", &row.total)?;
+            expected.balances.insert(
+                (client, currency),
+                Balance {
+                    available,
+                    held: amount("held", &row.held)?,
+                    authorized: Decimal::zero(),
+                    total,
+                    locked: row.locked,
+                    closed: false,
+                    negative: available < Decimal::zero() || total < Decimal::zero(),
+                },
+            );
+        }
+        Ok(expected)
+    }
+
+    /// Compares the expected balances with actual ones, returning every difference ordered by
+    /// client and currency. Amounts that differ by no more than the tolerance match.
+    ///
+    /// # Arguments
+    ///
+    /// * `actual` - The balance of each account rendered from the transactions, by client and
+    ///   currency.
+    /// * `tolerance` - The largest difference between amounts that still counts as a match.
+    pub fn reconcile(
+        &self,
+        actual: &BTreeMap<(Identifier, Option<Currency>), Balance>,
+        tolerance: Decimal,
+    ) -> Vec<Discrepancy> {
+        let mut keys: Vec<&(Identifier, Option<Currency>)> =
+            self.balances.keys().chain(actual.keys()).collect();
+        keys.sort();
+        keys.dedup();
+
+        let mut discrepancies = Vec::new();
+        for key in keys {
+            let (client, currency) = key;
+            let discrepancy = |field, expected: String, actual: String, difference| Discrepancy {
+                client: client.clone(),
+                currency: currency.clone(),
+                field,
+                expected,
+                actual,
+                difference,
+            };
+            let (expected, actual) = match (self.balances.get(key), actual.get(key)) {
+                (Some(expected), Some(actual)) => (expected, actual),
+                (expected, _) => {
+                    let (wanted, found) = match expected {
+                        Some(_) => ("present", "absent"),
+                        None => ("absent", "present"),
+                    };
+                    discrepancies.push(discrepancy(
+                        "account",
+                        String::from(wanted),
+                        String::from(found),
+                        None,
+                    ));
+                    continue;
+                }
+            };
+            for (field, wanted, found) in [
+                ("available", expected.available, actual.available),
+                ("held", expected.held, actual.held),
+                ("total", expected.total, actual.total),
+            ] {
+                if (found - wanted).abs() > tolerance {
+                    discrepancies.push(discrepancy(
+                        field,
+                        wanted.to_string(),
+                        found.to_string(),
+                        Some(found - wanted),
+                    ));
+                }
+            }
+            if expected.locked != actual.locked {
+                discrepancies.push(discrepancy(
+                    "locked",
+                    expected.locked.to_string(),
+                    actual.locked.to_string(),
+                    None,
+                ));
+            }
+        }
+        discrepancies
+    }
+}
+
+impl Discrepancy {
+    /// Writes discrepancies as CSV into a provided target that implements the `Write` trait.
+    ///
+    /// # Arguments
+    ///
+    /// * `discrepancies` - The discrepancies to write.
+    /// * `writer` - Anything that implements the Write trait.
+    pub fn write_csv(discrepancies: &[Discrepancy], writer: impl std::io::Write) -> Result<()> {
+        let mut csv_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(writer);
+        csv_writer
+            .write_record([
+                "client",
+                "currency",
+                "field",
+                "expected",
+                "actual",
+                "difference",
+            ])
+            .context("Failed to write discrepancies header.")?;
+        for discrepancy in discrepancies {
+            csv_writer
+                .serialize(discrepancy)
+                .context("Failed to serialize discrepancy to CSV writer.")?;
+        }
+        csv_writer
+            .flush()
+            .context("Writer failed to write discrepancies.")
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balance(available: i64, held: i64, locked: bool) -> Balance {
+        Balance {
+            available: Decimal::new(available, 1),
+            held: Decimal::new(held, 1),
+            authorized: Decimal::zero(),
+            total: Decimal::new(available + held, 1),
+            locked,
+            closed: false,
+            negative: false,
+        }
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let expected = ExpectedBalances::from_csv_data(
+            b"client,currency,available,held,total,locked,closed,negative,flags\n\
+              1,,1.0,0.0,1.0,false,false,false,\n\
+              2,USD,5.0,1.0,6.0,false,false,false,\n\
+              3,,0.0,0.0,0.0,true,false,false,\n",
+        )
+        .unwrap();
+        let mut actual = BTreeMap::new();
+        actual.insert((1.into(), None), balance(10, 0, false));
+        actual.insert(
+            (2.into(), Some("USD".parse().unwrap())),
+            balance(51, 10, true),
+        );
+        actual.insert((4.into(), None), balance(0, 0, false));
+
+        let fields = |discrepancies: Vec<Discrepancy>| -> Vec<(Identifier, &'static str)> {
+            discrepancies
+                .into_iter()
+                .map(|discrepancy| (discrepancy.client, discrepancy.field))
+                .collect()
+        };
+        assert_eq!(
+            fields(expected.reconcile(&actual, Decimal::zero())),
+            vec![
+                (2.into(), "available"),
+                (2.into(), "total"),
+                (2.into(), "locked"),
+                (3.into(), "account"),
+                (4.into(), "account"),
+            ]
+        );
+        assert_eq!(
+            fields(expected.reconcile(&actual, Decimal::new(1, 1))),
+            vec![
+                (2.into(), "locked"),
+                (3.into(), "account"),
+                (4.into(), "account")
+            ]
+        );
+
+        let discrepancies = expected.reconcile(&actual, Decimal::zero());
+        assert_eq!(discrepancies[0].expected, "5.0");
+        assert_eq!(discrepancies[0].actual, "5.1");
+        assert_eq!(discrepancies[0].difference, Some(Decimal::new(1, 1)));
+        assert_eq!(discrepancies[3].expected, "present");
+        assert_eq!(discrepancies[3].actual, "absent");
+    }
+
+    #[test]
+    fn test_expected_balances_from_csv() {
+        assert!(ExpectedBalances::from_csv_data(
+            b"client,available,held,total,locked\n1,1.0,0,1.0,false\n"
+        )
+        .is_ok());
+        assert!(ExpectedBalances::from_csv_data(
+            b"client,currency,available,held,total,locked\n1,dollars,1.0,0,1.0,false\n"
+        )
+        .is_err());
+        assert!(ExpectedBalances::from_csv_data(b"client,available,total\n1,1.0,1.0\n").is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use super::{Currency, Identifier, Transaction};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Reasons an Account can refuse to apply a transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rejection {
+    /// The transaction belongs to a different client.
+    WrongClient,
+    /// The account is locked and cannot process transactions.
+    AccountLocked,
+    /// The account does not have enough available funds.
+    InsufficientFunds,
+    /// A transaction with the same identifier has already been applied to the account.
+    DuplicateTransaction,
+    /// The referenced transaction is not in the account history.
+    UnknownTransaction,
+    /// The referenced transaction is already under dispute.
+    AlreadyDisputed,
+    /// The referenced transaction is not under dispute.
+    NotDisputed,
+    /// The dispute was opened too long after the referenced transaction.
+    DisputeWindowClosed,
+    /// The referenced transaction cannot be disputed.
+    NotDisputable,
+    /// The disputed amount is larger than the part of the referenced transaction that can still
+    /// be disputed.
+    ExceedsDisputableAmount,
+    /// The amount is larger than the part of the referenced transaction under dispute.
+    ExceedsDisputedAmount,
+    /// The Policy does not authorize the transaction.
+    Unauthorized,
+    /// The account is not locked.
+    NotLocked,
+    /// The account is closed and cannot process transactions.
+    AccountClosed,
+    /// The account is not closed.
+    NotClosed,
+    /// The account still holds funds or owes them.
+    BalanceNotZero,
+    /// The transaction involves a second account and must be applied to both together.
+    CounterpartyRequired,
+    /// The transfer names the same client as its source and destination.
+    SelfTransfer,
+    /// The referenced transaction is not an open authorization.
+    NotAuthorized,
+    /// The amount is larger than the part of the referenced authorization still held.
+    ExceedsAuthorizedAmount,
+    /// The transaction is in a different currency from the account or the transaction it refers
+    /// to.
+    CurrencyMismatch,
+    /// The withdrawal is larger than the client's single withdrawal limit.
+    ExceedsWithdrawalLimit,
+    /// The withdrawal would take the client's withdrawals for the day over their daily limit.
+    ExceedsDailyWithdrawalLimit,
+    /// The withdrawal would take the client's withdrawals over their rolling limit.
+    ExceedsRollingWithdrawalLimit,
+    /// The client has already made as many transactions as their limit allows in the window.
+    TooManyTransactions,
+    /// The deposit would take the account total over the client's maximum balance.
+    ExceedsBalanceLimit,
+    /// The opening balance comes after other transactions on the account.
+    OpeningNotFirst,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Rejection::WrongClient => "transaction belongs to a different client",
+            Rejection::AccountLocked => "account is locked",
+            Rejection::InsufficientFunds => "insufficient available funds",
+            Rejection::DuplicateTransaction => "transaction identifier already used",
+            Rejection::UnknownTransaction => "referenced transaction not found",
+            Rejection::AlreadyDisputed => "referenced transaction is already disputed",
+            Rejection::NotDisputed => "referenced transaction is not disputed",
+            Rejection::DisputeWindowClosed => "dispute window has closed",
+            Rejection::NotDisputable => "referenced transaction cannot be disputed",
+            Rejection::ExceedsDisputableAmount => "amount exceeds the remaining disputable amount",
+            Rejection::ExceedsDisputedAmount => "amount exceeds the disputed amount",
+            Rejection::Unauthorized => "transaction is not authorized by the policy",
+            Rejection::NotLocked => "account is not locked",
+            Rejection::AccountClosed => "account is closed",
+            Rejection::NotClosed => "account is not closed",
+            Rejection::BalanceNotZero => "account balance is not zero",
+            Rejection::CounterpartyRequired => "transaction must be applied to both accounts",
+            Rejection::SelfTransfer => "transfer source and destination are the same client",
+            Rejection::NotAuthorized => "referenced transaction is not an open authorization",
+            Rejection::ExceedsAuthorizedAmount => "amount exceeds the authorized amount",
+            Rejection::CurrencyMismatch => "transaction currency differs from the account currency",
+            Rejection::ExceedsWithdrawalLimit => "amount exceeds the single withdrawal limit",
+            Rejection::ExceedsDailyWithdrawalLimit => "withdrawals exceed the daily limit",
+            Rejection::ExceedsRollingWithdrawalLimit => "withdrawals exceed the rolling limit",
+            Rejection::TooManyTransactions => "transaction count exceeds the limit for the window",
+            Rejection::ExceedsBalanceLimit => "balance would exceed the maximum balance",
+            Rejection::OpeningNotFirst => "opening balance must precede every other transaction",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+/// A transaction the Ledger refused to apply, recorded for review.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RejectedTransaction {
+    /// Client identifier named by the transaction.
+    pub client: Identifier,
+    /// Currency of the account the transaction was offered to, if known.
+    pub currency: Option<Currency>,
+    /// Transaction identifier.
+    pub tx: Identifier,
+    /// When the transaction occurred, if known.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Name of the transaction type.
+    #[serde(rename = "type")]
+    pub transaction_type: &'static str,
+    /// Amount carried by the transaction, if any.
+    pub amount: Option<Decimal>,
+    /// Why the transaction was rejected.
+    pub reason: String,
+}
+
+impl RejectedTransaction {
+    /// Records a rejected transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - The rejected transaction.
+    /// * `currency` - Currency of the account the transaction was offered to, if known.
+    /// * `rejection` - Why the transaction was rejected.
+    pub fn new(
+        transaction: &Transaction,
+        currency: Option<Currency>,
+        rejection: &Rejection,
+    ) -> RejectedTransaction {
+        RejectedTransaction {
+            client: transaction.client.clone(),
+            currency,
+            tx: transaction.tx.clone(),
+            timestamp: transaction.timestamp,
+            transaction_type: transaction.transaction_type.name(),
+            amount: transaction.transaction_type.amount(),
+            reason: rejection.to_string(),
+        }
+    }
+
+    /// Writes rejected transactions as CSV into a provided target that implements the `Write`
+    /// trait.
+    ///
+    /// # Arguments
+    ///
+    /// * `rejections` - The rejected transactions to write, in the order they were offered.
+    /// * `writer` - Anything that implements the Write trait.
+    pub fn write_csv(
+        rejections: &[RejectedTransaction],
+        writer: impl std::io::Write,
+    ) -> Result<()> {
+        let mut csv_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(writer);
+        csv_writer
+            .write_record([
+                "client",
+                "currency",
+                "tx",
+                "timestamp",
+                "type",
+                "amount",
+                "reason",
+            ])
+            .context("Failed to write rejections header.")?;
+        for rejection in rejections {
+            csv_writer
+                .serialize(rejection)
+                .context("Failed to serialize rejected transaction to CSV writer.")?;
+        }
+        csv_writer
+            .flush()
+            .context("Writer failed to write rejected transactions.")
+    }
+}
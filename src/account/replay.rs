@@ -0,0 +1,155 @@
+use super::{Account, Balance, Currency, Identifier, Rejection, Transaction};
+use chrono::{DateTime, Utc};
+
+/// A single step of a replay: a transaction offered to the account and the balance directly
+/// after it was resolved.
+#[derive(Debug, Clone)]
+pub struct Step {
+    /// Client identifier of the account the step applies to.
+    pub client: Identifier,
+    /// Currency of the account the step applies to, if known.
+    pub currency: Option<Currency>,
+    /// The transaction offered to the account at this step.
+    pub transaction: Transaction,
+    /// Whether the transaction was applied, or the reason it was rejected.
+    pub outcome: Result<(), Rejection>,
+    /// The account balance after the transaction was resolved.
+    pub balance: Balance,
+    /// Whether the account generated the transaction itself, such as when closing an expired
+    /// dispute.
+    pub automatic: bool,
+}
+
+/// Walks the history of an Account one transaction at a time, yielding the balance after each
+/// step. The account built so far can be inspected at any point.
+#[derive(Debug)]
+pub struct Replay {
+    account: Account,
+    transactions: std::vec::IntoIter<Transaction>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl Replay {
+    /// Creates a replay of the provided transactions starting from the provided account.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - The account to apply the transactions to.
+    /// * `transactions` - A series of transactions for the account's client in chronological
+    ///   order.
+    pub fn new(account: Account, transactions: Vec<Transaction>) -> Replay {
+        Replay {
+            account,
+            transactions: transactions.into_iter(),
+            until: None,
+        }
+    }
+
+    /// Continues the replay past the last transaction up to the provided time, so that disputes
+    /// expiring in the meantime are closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `until` - The time the replay ends at. The replay ends with the last transaction if
+    ///   `None`.
+    pub fn until(mut self, until: Option<DateTime<Utc>>) -> Replay {
+        self.until = until;
+        self
+    }
+
+    /// The account as it stands after the steps taken so far.
+    pub fn account(&self) -> &Account {
+        &self.account
+    }
+
+    /// Consumes the replay, returning the account as it stands after the steps taken so far.
+    pub fn into_account(self) -> Account {
+        self.account
+    }
+
+    /// Builds a Step from the current account balance.
+    fn step(
+        &self,
+        transaction: Transaction,
+        outcome: Result<(), Rejection>,
+        automatic: bool,
+    ) -> Step {
+        Step {
+            client: self.account.client().clone(),
+            currency: self.account.currency().cloned(),
+            transaction,
+            outcome,
+            balance: self.account.balance(),
+            automatic,
+        }
+    }
+}
+
+impl Iterator for Replay {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        // Disputes that expire before the next transaction occurs are closed first, each as its
+        // own step.
+        let now = match self.transactions.as_slice().first() {
+            Some(next) => next.timestamp,
+            None => self.until,
+        };
+        if let Some((expired, outcome)) = now.and_then(|now| self.account.expire_next(now)) {
+            return Some(self.step(expired, outcome, true));
+        }
+        let transaction = self.transactions.next()?;
+        let outcome = self.account.resolve_new_transaction(transaction.clone());
+        Some(self.step(transaction, outcome, false))
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{TransactionSet, TransactionType};
+    use rust_decimal::prelude::*;
+
+    #[test]
+    fn test_replay_yields_balance_after_each_step() {
+        let replay = Account::replay(TransactionSet {
+            transactions: vec![
+                Transaction {
+                    transaction_type: TransactionType::Deposit(Decimal::new(5, 0)),
+                    tx: 1.into(),
+                    client: 4.into(),
+                    timestamp: None,
+                    currency: None,
+                },
+                Transaction {
+                    transaction_type: TransactionType::Withdraw(Decimal::new(2, 0)),
+                    tx: 2.into(),
+                    client: 4.into(),
+                    timestamp: None,
+                    currency: None,
+                },
+                Transaction {
+                    transaction_type: TransactionType::Dispute(None),
+                    tx: 1.into(),
+                    client: 4.into(),
+                    timestamp: None,
+                    currency: None,
+                },
+            ],
+            client: 4.into(),
+        });
+        let steps: Vec<Step> = replay.collect();
+        let balances: Vec<Balance> = steps.iter().map(|step| step.balance).collect();
+
+        assert_eq!(balances.len(), 3);
+        assert!(steps.iter().all(|step| step.outcome.is_ok()));
+        assert_eq!(balances[0].available, Decimal::new(5, 0));
+        assert_eq!(balances[1].available, Decimal::new(3, 0));
+        assert_eq!(balances[1].total, Decimal::new(3, 0));
+        assert_eq!(balances[2].available, Decimal::new(-2, 0));
+        assert_eq!(balances[2].held, Decimal::new(5, 0));
+        assert_eq!(balances[2].total, Decimal::new(3, 0));
+    }
+}
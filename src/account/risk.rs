@@ -0,0 +1,176 @@
+use super::{Account, Currency, Identifier, Transaction, TransactionType};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A heuristic that inspects every transaction applied to an account and fires when the account
+/// looks suspicious. Rules are evaluated by the Ledger once the transaction has been applied, so
+/// the account history already includes it.
+pub trait RiskRule: fmt::Debug {
+    /// Name of the rule, recorded with every firing and shown in the `flags` column.
+    fn name(&self) -> &str;
+
+    /// Indicates whether the rule fires for a transaction that was just applied to the account.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - The account after the transaction was applied.
+    /// * `transaction` - The applied transaction.
+    fn fires(&self, account: &Account, transaction: &Transaction) -> bool;
+}
+
+/// What happens to an account when a rule fires.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskAction {
+    /// Add the rule name to the account's `flags` column.
+    #[default]
+    Flag,
+    /// Flag the account and lock it pre-emptively.
+    Lock,
+}
+
+/// A single firing of a risk rule, recorded for review.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RiskEvent {
+    /// Client identifier of the account the rule fired on.
+    pub client: Identifier,
+    /// Currency of the account, if known.
+    pub currency: Option<Currency>,
+    /// Identifier of the transaction that triggered the rule.
+    pub tx: Identifier,
+    /// When the transaction occurred, if known.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Name of the rule.
+    pub rule: String,
+    /// What was done to the account.
+    pub action: RiskAction,
+}
+
+impl RiskEvent {
+    /// Writes risk events as CSV into a provided target that implements the `Write` trait.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The events to write, in the order they were recorded.
+    /// * `writer` - Anything that implements the Write trait.
+    pub fn write_csv(events: &[RiskEvent], writer: impl std::io::Write) -> Result<()> {
+        let mut csv_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(writer);
+        csv_writer
+            .write_record(["client", "currency", "tx", "timestamp", "rule", "action"])
+            .context("Failed to write risk log header.")?;
+        for event in events {
+            csv_writer
+                .serialize(event)
+                .context("Failed to serialize risk event to CSV writer.")?;
+        }
+        csv_writer
+            .flush()
+            .context("Writer failed to write risk events.")
+    }
+}
+
+/// Fires on a dispute when the account has opened at least `count` disputes within the window,
+/// including this one. Only disputes that carry timestamps are counted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepeatedDisputes {
+    /// How many disputes within the window fire the rule.
+    pub count: u32,
+    /// Length of the window in hours.
+    pub window_hours: u32,
+}
+
+/// Fires on a withdrawal that directly follows a deposit and takes at least the deposited
+/// amount. When both carry timestamps, the withdrawal must also come within the window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DepositThenWithdrawal {
+    /// How many minutes after the deposit the withdrawal must come.
+    pub within_minutes: u32,
+}
+
+/// Fires on a dispute of a transaction that occurred more than the threshold before the dispute.
+/// Only applies when both carry timestamps.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StaleDispute {
+    /// How many days old the disputed transaction must be.
+    pub older_than_days: u32,
+}
+
+impl RiskRule for RepeatedDisputes {
+    fn name(&self) -> &str {
+        "repeated_disputes"
+    }
+
+    fn fires(&self, account: &Account, transaction: &Transaction) -> bool {
+        let now = match (&transaction.transaction_type, transaction.timestamp) {
+            (TransactionType::Dispute(_), Some(now)) => now,
+            _ => return false,
+        };
+        let start = now - Duration::hours(self.window_hours.into());
+        let disputes = account
+            .transactions()
+            .iter()
+            .filter(|txn| matches!(txn.transaction_type, TransactionType::Dispute(_)))
+            .filter(|txn| {
+                txn.timestamp
+                    .is_some_and(|time| time > start && time <= now)
+            })
+            .count();
+        disputes >= self.count as usize
+    }
+}
+
+impl RiskRule for DepositThenWithdrawal {
+    fn name(&self) -> &str {
+        "deposit_then_withdrawal"
+    }
+
+    fn fires(&self, account: &Account, transaction: &Transaction) -> bool {
+        let withdrawn = match transaction.transaction_type {
+            TransactionType::Withdraw(amount) => amount,
+            _ => return false,
+        };
+        let history = account.transactions();
+        let previous = match history.len().checked_sub(2).map(|index| &history[index]) {
+            Some(previous) => previous,
+            None => return false,
+        };
+        let deposited = match previous.transaction_type {
+            TransactionType::Deposit(amount) => amount,
+            _ => return false,
+        };
+        let within = match (previous.timestamp, transaction.timestamp) {
+            (Some(deposited_at), Some(withdrawn_at)) => {
+                withdrawn_at - deposited_at <= Duration::minutes(self.within_minutes.into())
+            }
+            _ => true,
+        };
+        within && deposited > Decimal::zero() && withdrawn >= deposited
+    }
+}
+
+impl RiskRule for StaleDispute {
+    fn name(&self) -> &str {
+        "stale_dispute"
+    }
+
+    fn fires(&self, account: &Account, transaction: &Transaction) -> bool {
+        let now = match (&transaction.transaction_type, transaction.timestamp) {
+            (TransactionType::Dispute(_), Some(now)) => now,
+            _ => return false,
+        };
+        account
+            .transactions()
+            .iter()
+            .find(|txn| txn.tx == transaction.tx)
+            .and_then(|disputed| disputed.timestamp)
+            .is_some_and(|occurred| now - occurred > Duration::days(self.older_than_days.into()))
+    }
+}
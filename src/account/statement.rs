@@ -0,0 +1,71 @@
+use super::{Currency, Identifier, Step, TransactionType};
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::*;
+use serde::Serialize;
+
+/// A single row of an account statement: a transaction, its outcome and the running balance
+/// directly after it.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatementRow {
+    /// Transaction identifier.
+    pub tx: Identifier,
+    /// When the transaction occurred, if known.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Name of the transaction type.
+    #[serde(rename = "type")]
+    pub transaction_type: &'static str,
+    /// Amount carried by the transaction, if any.
+    pub amount: Option<Decimal>,
+    /// Currency of the account, if known.
+    pub currency: Option<Currency>,
+    /// Either `applied`, `rejected`, or `automatic` for transactions the account generated itself.
+    pub outcome: &'static str,
+    /// Why the transaction was rejected or generated, if it was. For administrative transactions,
+    /// the operator and their reason, and for adjustments, their reference.
+    pub reason: Option<String>,
+    /// The funds available after this transaction.
+    pub available: Decimal,
+    /// The funds held for dispute after this transaction.
+    pub held: Decimal,
+    /// The funds in all states after this transaction.
+    pub total: Decimal,
+    /// Whether the account was locked after this transaction.
+    pub locked: bool,
+}
+
+impl From<&Step> for StatementRow {
+    fn from(step: &Step) -> StatementRow {
+        let (outcome, reason) = match &step.outcome {
+            Ok(()) if step.automatic => match step.transaction.transaction_type {
+                TransactionType::Void => ("automatic", Some(String::from("authorization expired"))),
+                _ => ("automatic", Some(String::from("dispute expired"))),
+            },
+            Ok(()) => (
+                "applied",
+                match &step.transaction.transaction_type {
+                    TransactionType::Adjustment { reference, .. } => Some(reference.clone()),
+                    transaction_type => transaction_type.admin_action().map(|action| match &action
+                        .reason
+                    {
+                        Some(reason) => format!("{}: {}", action.operator, reason),
+                        None => action.operator.clone(),
+                    }),
+                },
+            ),
+            Err(rejection) => ("rejected", Some(rejection.to_string())),
+        };
+        StatementRow {
+            tx: step.transaction.tx.clone(),
+            timestamp: step.transaction.timestamp,
+            transaction_type: step.transaction.transaction_type.name(),
+            amount: step.transaction.transaction_type.amount(),
+            currency: step.currency.clone(),
+            outcome,
+            reason,
+            available: step.balance.available,
+            held: step.balance.held,
+            total: step.balance.total,
+            locked: step.balance.locked,
+        }
+    }
+}
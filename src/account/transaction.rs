@@ -1,4 +1,5 @@
-use super::TransactionType;
+use super::{Currency, Identifier, ParseError, ParseErrorKind, TransactionType};
+use chrono::{DateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 
 /// A single transaction. Generally, part of a series of transactions used to
@@ -9,7 +10,103 @@ pub struct Transaction {
     /// associated account.
     pub transaction_type: TransactionType,
     /// Transaction identifier.
-    pub tx: u32,
+    pub tx: Identifier,
     /// Client identifier
-    pub client: u16,
+    pub client: Identifier,
+    /// When the transaction occurred, if the source provided it.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Currency of the transaction, if the source provided it. Transactions that refer to an
+    /// earlier transaction, such as disputes, take its currency if they carry none.
+    pub currency: Option<Currency>,
+}
+
+impl Transaction {
+    /// Generate a Transaction from a CSV record.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - Header data from the CSV file. This is used to dynamically access the record
+    ///   data regardless of column order. It only depends on the column names being known and
+    ///   consistent.
+    /// * `record` - A StringRecord data row containing transaction data to be parsed into a
+    ///   Transaction.
+    pub fn from_record(
+        headers: &csv::StringRecord,
+        record: &csv::StringRecord,
+    ) -> Result<Transaction, ParseError> {
+        let transaction_type = TransactionType::from_record(headers, record)?;
+        let tx = match Transaction::field_from_record(headers, record, "tx") {
+            Some(value) => value
+                .parse::<Identifier>()
+                .map_err(|error| format!("Failed to parse transaction identifier. {}", error))?,
+            None => {
+                return Err(ParseError::new(
+                    ParseErrorKind::InvalidRow,
+                    "Missing transaction identifier.",
+                ))
+            }
+        };
+        let client = match Transaction::field_from_record(headers, record, "client") {
+            Some(value) => value
+                .parse::<Identifier>()
+                .map_err(|error| format!("Failed to parse client identifier. {}", error))?,
+            None => {
+                return Err(ParseError::new(
+                    ParseErrorKind::InvalidRow,
+                    "Missing client identifier.",
+                ))
+            }
+        };
+        let timestamp = match Transaction::field_from_record(headers, record, "timestamp") {
+            Some(value) if !value.is_empty() => Some(Transaction::parse_timestamp(value)?),
+            _ => None,
+        };
+        let currency = match Transaction::field_from_record(headers, record, "currency") {
+            Some(value) if !value.is_empty() => Some(value.parse::<Currency>()?),
+            _ => None,
+        };
+        Ok(Transaction {
+            transaction_type,
+            tx,
+            client,
+            timestamp,
+            currency,
+        })
+    }
+
+    /// Parses a timestamp given either as an RFC 3339 string or as whole seconds since the Unix
+    /// epoch.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The timestamp text.
+    pub fn parse_timestamp(value: &str) -> Result<DateTime<Utc>, String> {
+        if let Ok(seconds) = value.parse::<i64>() {
+            return Utc
+                .timestamp_opt(seconds, 0)
+                .single()
+                .ok_or_else(|| String::from("Timestamp is out of range."));
+        }
+        DateTime::parse_from_rfc3339(value)
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+            .map_err(|_| String::from("Failed to parse transaction timestamp."))
+    }
+
+    /// Returns the value of a named column from the provided record, if present.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - Header data from the CSV file.
+    /// * `record` - A StringRecord data row.
+    /// * `name` - The column name to look up.
+    fn field_from_record<'a>(
+        headers: &csv::StringRecord,
+        record: &'a csv::StringRecord,
+        name: &str,
+    ) -> Option<&'a str> {
+        match headers.iter().position(|x| x == name) {
+            Some(index) => record.get(index),
+            None => None,
+        }
+    }
 }
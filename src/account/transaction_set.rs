@@ -1,6 +1,5 @@
-use super::{Transaction, TransactionType};
+use super::{Identifier, Input, InputOptions, Transaction};
 use anyhow::{Error, Result};
-use csv;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
@@ -10,7 +9,7 @@ pub struct TransactionSet {
     /// Group of transactions with the same client
     pub transactions: Vec<Transaction>,
     /// Client identifier
-    pub client: u16,
+    pub client: Identifier,
 }
 
 impl TransactionSet {
@@ -18,42 +17,22 @@ impl TransactionSet {
     /// # Arguments
     ///
     /// * `data` - Reference to a Vec<u8> of CSV data. The argument type makes this method ready
-    /// to process CSV file data from any source.
+    ///   to process CSV file data from any source.
     pub fn transaction_sets_from_csv_data(data: &[u8]) -> Result<Vec<TransactionSet>, Error> {
-        let mut csv_results = csv::ReaderBuilder::new()
-            .trim(csv::Trim::All)
-            .from_reader(data);
-        let headers = csv_results.headers()?.clone();
-        let grouped_transactions = csv_results
-            .records()
-            .filter_map(|result| match result {
-                Ok(record) => {
-                    let transaction_type = TransactionType::from_record(&headers, &record);
-                    let tx_opt = match headers.iter().position(|x| x == "tx") {
-                        Some(index) => record.get(index),
-                        None => None,
-                    };
-                    let client_opt = match headers.iter().position(|x| x == "client") {
-                        Some(index) => record.get(index),
-                        None => None,
-                    };
-                    // These unwraps could be eliminated with nested `if let` or `match` blocks. If
-                    // the assumptions regarding source data change to allow more loose constraints
-                    // this could be necessary.
-                    if transaction_type.is_ok() && tx_opt.is_some() && client_opt.is_some() {
-                        Some(Transaction {
-                            transaction_type: transaction_type.unwrap(),
-                            tx: tx_opt.unwrap().parse::<u32>().unwrap(),
-                            client: client_opt.unwrap().parse::<u16>().unwrap(),
-                        })
-                    } else {
-                        None
-                    }
-                }
-                Err(_) => None,
-            })
-            .sorted_by_key(|transaction| transaction.client)
-            .group_by(|transaction| transaction.client);
+        Ok(Input::from_csv_data(data, &InputOptions::default())?.transaction_sets())
+    }
+
+    /// Groups transactions into one TransactionSet per client. Transactions keep their relative
+    /// order within each set.
+    ///
+    /// # Arguments
+    ///
+    /// * `transactions` - Transactions for any number of clients in application order.
+    pub fn from_transactions(transactions: Vec<Transaction>) -> Vec<TransactionSet> {
+        let grouped_transactions = transactions
+            .into_iter()
+            .sorted_by_key(|transaction| transaction.client.clone())
+            .group_by(|transaction| transaction.client.clone());
 
         let mut transaction_sets = Vec::new();
         for (key, group) in &grouped_transactions {
@@ -62,6 +41,6 @@ impl TransactionSet {
                 client: key,
             });
         }
-        Ok(transaction_sets)
+        transaction_sets
     }
 }
@@ -1,36 +1,131 @@
-use csv;
+use super::{AdminAction, Identifier, ParseError, ParseErrorKind};
 use rust_decimal::prelude::*;
 use serde::{Deserialize, Serialize};
 
 /// Set of possible transaction types.
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TransactionType {
     /// Removal of some amount from a Client
     Withdraw(Decimal),
     /// Addition of some amount to a Client
     Deposit(Decimal),
-    /// Freeze a transaction
-    Dispute,
-    /// Unfreeze a disputed transaction
-    Resolve,
-    /// User-initiated action that results in an account lock
-    Chargeback,
+    /// Movement of some amount from a Client to another Client
+    Transfer {
+        /// Client identifier of the receiving Client
+        destination: Identifier,
+        /// Amount moved
+        amount: Decimal,
+    },
+    /// Charge taken from a Client by the operator
+    Fee(Decimal),
+    /// Interest credited to a Client. Cannot be disputed
+    Interest(Decimal),
+    /// Signed correction of a Client's available funds. Cannot be disputed
+    Adjustment {
+        /// Amount added to the available funds. Negative amounts remove funds
+        amount: Decimal,
+        /// Reference to the record justifying the correction
+        reference: String,
+    },
+    /// Hold some amount of a Client's funds until it is captured or voided
+    Authorize(Decimal),
+    /// Finalize an authorization as a withdrawal, in full or the given portion of it
+    Capture(Option<Decimal>),
+    /// Release what remains of an authorization
+    Void,
+    /// Freeze a transaction, or the given portion of it
+    Dispute(Option<Decimal>),
+    /// Unfreeze a disputed transaction, or the given portion of the disputed amount
+    Resolve(Option<Decimal>),
+    /// User-initiated action that results in an account lock. Charges back the disputed amount,
+    /// or the given portion of it
+    Chargeback(Option<Decimal>),
+    /// Administrative reinstatement of a locked account
+    Unlock(AdminAction),
+    /// Administrative closure of an account with a zero balance
+    Close(AdminAction),
+    /// Administrative reopening of a closed account
+    Reopen(AdminAction),
+    /// Balance carried over from another system, applied before any other transaction. Cannot be
+    /// disputed
+    Opening {
+        /// Funds available at the start of the history
+        available: Decimal,
+        /// Funds held at the start of the history
+        held: Decimal,
+        /// Identifier of the transaction the held funds were held under in the other system, which
+        /// a resolve or chargeback names to release them
+        held_tx: Option<Identifier>,
+        /// Whether the account starts locked
+        locked: bool,
+    },
 }
 
 impl TransactionType {
+    /// The name of the transaction type as it appears in CSV data.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionType::Withdraw(_) => "withdraw",
+            TransactionType::Deposit(_) => "deposit",
+            TransactionType::Transfer { .. } => "transfer",
+            TransactionType::Fee(_) => "fee",
+            TransactionType::Interest(_) => "interest",
+            TransactionType::Adjustment { .. } => "adjustment",
+            TransactionType::Authorize(_) => "authorize",
+            TransactionType::Capture(_) => "capture",
+            TransactionType::Void => "void",
+            TransactionType::Dispute(_) => "dispute",
+            TransactionType::Resolve(_) => "resolve",
+            TransactionType::Chargeback(_) => "chargeback",
+            TransactionType::Unlock(_) => "unlock",
+            TransactionType::Close(_) => "close",
+            TransactionType::Reopen(_) => "reopen",
+            TransactionType::Opening { .. } => "opening",
+        }
+    }
+
+    /// The amount carried by the transaction type, if any. An opening balance sets the state of
+    /// an account rather than moving an amount, so it has none.
+    pub fn amount(&self) -> Option<Decimal> {
+        match self {
+            TransactionType::Withdraw(amount)
+            | TransactionType::Deposit(amount)
+            | TransactionType::Transfer { amount, .. }
+            | TransactionType::Fee(amount)
+            | TransactionType::Interest(amount)
+            | TransactionType::Adjustment { amount, .. }
+            | TransactionType::Authorize(amount) => Some(*amount),
+            TransactionType::Capture(amount)
+            | TransactionType::Dispute(amount)
+            | TransactionType::Resolve(amount)
+            | TransactionType::Chargeback(amount) => *amount,
+            _ => None,
+        }
+    }
+
+    /// The operator and reason carried by administrative transaction types.
+    pub fn admin_action(&self) -> Option<&AdminAction> {
+        match self {
+            TransactionType::Unlock(action)
+            | TransactionType::Close(action)
+            | TransactionType::Reopen(action) => Some(action),
+            _ => None,
+        }
+    }
+
     /// Generate a TransactionType with any optional abount data from a CSV record
     ///
     /// # Arguments
     ///
     /// * `headers` - Header data from the CSV file. This is used to dynamically access the record
-    /// data regardless of column order. It only depends on the column names being known and
-    /// consistent.
+    ///   data regardless of column order. It only depends on the column names being known and
+    ///   consistent.
     /// * `record` - A StringRecord data row containing transaction data to be parsed into a
-    /// TransactionType.
+    ///   TransactionType.
     pub fn from_record(
         headers: &csv::StringRecord,
         record: &csv::StringRecord,
-    ) -> Result<TransactionType, String> {
+    ) -> Result<TransactionType, ParseError> {
         let type_indicator = match headers.iter().position(|x| x == "type") {
             Some(index) => record.get(index),
             None => None,
@@ -38,25 +133,147 @@ impl TransactionType {
         match type_indicator {
             Some(indicator) => match indicator {
                 "deposit" => {
-                    if let Some(decimal) = TransactionType::amount_from_record(headers, record) {
+                    if let Some(decimal) =
+                        TransactionType::unsigned_amount_from_record(headers, record)
+                    {
                         Ok(TransactionType::Deposit(decimal))
                     } else {
-                        Err(format!("Failed to parse deposit transaction amount."))
+                        Err(ParseError::new(
+                            ParseErrorKind::BadAmount,
+                            "Failed to parse deposit transaction amount.",
+                        ))
                     }
                 }
                 "withdraw" => {
-                    if let Some(decimal) = TransactionType::amount_from_record(headers, record) {
+                    if let Some(decimal) =
+                        TransactionType::unsigned_amount_from_record(headers, record)
+                    {
                         Ok(TransactionType::Withdraw(decimal))
                     } else {
-                        Err(format!("Failed to parse withdraw transaction amount."))
+                        Err(ParseError::new(
+                            ParseErrorKind::BadAmount,
+                            "Failed to parse withdraw transaction amount.",
+                        ))
+                    }
+                }
+                "transfer" => {
+                    let destination = match headers.iter().position(|x| x == "destination") {
+                        Some(index) => record.get(index),
+                        None => None,
+                    };
+                    let destination = match destination.map(|value| value.parse::<Identifier>()) {
+                        Some(Ok(destination)) => destination,
+                        _ => {
+                            return Err(ParseError::new(
+                                ParseErrorKind::InvalidRow,
+                                "Failed to parse transfer destination client.",
+                            ))
+                        }
+                    };
+                    if let Some(amount) =
+                        TransactionType::unsigned_amount_from_record(headers, record)
+                    {
+                        Ok(TransactionType::Transfer {
+                            destination,
+                            amount,
+                        })
+                    } else {
+                        Err(ParseError::new(
+                            ParseErrorKind::BadAmount,
+                            "Failed to parse transfer transaction amount.",
+                        ))
                     }
                 }
-                "dispute" => Ok(TransactionType::Dispute),
-                "resolve" => Ok(TransactionType::Resolve),
-                "chargeback" => Ok(TransactionType::Chargeback),
-                _ => Err(format!("Unknown transaction type.")),
+                "fee" => {
+                    if let Some(decimal) =
+                        TransactionType::unsigned_amount_from_record(headers, record)
+                    {
+                        Ok(TransactionType::Fee(decimal))
+                    } else {
+                        Err(ParseError::new(
+                            ParseErrorKind::BadAmount,
+                            "Failed to parse fee transaction amount.",
+                        ))
+                    }
+                }
+                "interest" => {
+                    if let Some(decimal) =
+                        TransactionType::unsigned_amount_from_record(headers, record)
+                    {
+                        Ok(TransactionType::Interest(decimal))
+                    } else {
+                        Err(ParseError::new(
+                            ParseErrorKind::BadAmount,
+                            "Failed to parse interest transaction amount.",
+                        ))
+                    }
+                }
+                "adjustment" => {
+                    let reference = match headers.iter().position(|x| x == "reference") {
+                        Some(index) => record.get(index).filter(|value| !value.is_empty()),
+                        None => None,
+                    };
+                    match (
+                        TransactionType::amount_from_record(headers, record),
+                        reference,
+                    ) {
+                        (Some(amount), Some(reference)) => Ok(TransactionType::Adjustment {
+                            amount,
+                            reference: String::from(reference),
+                        }),
+                        (None, _) => Err(ParseError::new(
+                            ParseErrorKind::BadAmount,
+                            "Failed to parse adjustment transaction amount.",
+                        )),
+                        (_, None) => Err(ParseError::new(
+                            ParseErrorKind::InvalidRow,
+                            "Adjustments require a reference.",
+                        )),
+                    }
+                }
+                "authorize" => {
+                    if let Some(decimal) =
+                        TransactionType::unsigned_amount_from_record(headers, record)
+                    {
+                        Ok(TransactionType::Authorize(decimal))
+                    } else {
+                        Err(ParseError::new(
+                            ParseErrorKind::BadAmount,
+                            "Failed to parse authorize transaction amount.",
+                        ))
+                    }
+                }
+                "capture" => Ok(TransactionType::Capture(
+                    TransactionType::partial_amount_from_record(headers, record)?,
+                )),
+                "void" => Ok(TransactionType::Void),
+                "dispute" => Ok(TransactionType::Dispute(
+                    TransactionType::partial_amount_from_record(headers, record)?,
+                )),
+                "resolve" => Ok(TransactionType::Resolve(
+                    TransactionType::partial_amount_from_record(headers, record)?,
+                )),
+                "chargeback" => Ok(TransactionType::Chargeback(
+                    TransactionType::partial_amount_from_record(headers, record)?,
+                )),
+                "unlock" => Ok(TransactionType::Unlock(AdminAction::from_record(
+                    headers, record,
+                )?)),
+                "close" => Ok(TransactionType::Close(AdminAction::from_record(
+                    headers, record,
+                )?)),
+                "reopen" => Ok(TransactionType::Reopen(AdminAction::from_record(
+                    headers, record,
+                )?)),
+                _ => Err(ParseError::new(
+                    ParseErrorKind::UnknownType,
+                    "Unknown transaction type.",
+                )),
             },
-            None => Err(format!("Failed to parse transaction from provided data.")),
+            None => Err(ParseError::new(
+                ParseErrorKind::UnknownType,
+                "Failed to parse transaction from provided data.",
+            )),
         }
     }
 
@@ -65,8 +282,8 @@ impl TransactionType {
     /// # Arguments
     ///
     /// * `headers` - Header data from the CSV file. This is used to dynamically access the record
-    /// data regardless of column order. It only depends on the column names being known and
-    /// consistent.
+    ///   data regardless of column order. It only depends on the column names being known and
+    ///   consistent.
     /// * `record` - A StringRecord data row optionally containing an amount
     fn amount_from_record(
         headers: &csv::StringRecord,
@@ -77,11 +294,59 @@ impl TransactionType {
             None => None,
         };
         match amount_opt {
-            Some(value) => match Decimal::from_str(value) {
-                Ok(val) => Some(val),
-                Err(_) => None,
-            },
+            Some(value) => Decimal::from_str(value).ok(),
             None => None,
         }
     }
+
+    /// Parse the amount from the provided record, rejecting negative amounts. Only adjustments
+    /// may carry a negative amount; a negative deposit or authorization would otherwise drive held
+    /// funds below zero once it is disputed or captured.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - Header data from the CSV file. This is used to dynamically access the record
+    ///   data regardless of column order. It only depends on the column names being known and
+    ///   consistent.
+    /// * `record` - A StringRecord data row containing an amount
+    fn unsigned_amount_from_record(
+        headers: &csv::StringRecord,
+        record: &csv::StringRecord,
+    ) -> Option<Decimal> {
+        TransactionType::amount_from_record(headers, record)
+            .filter(|amount| *amount >= Decimal::zero())
+    }
+
+    /// Parse the optional amount of a dispute, resolve, chargeback or capture from the provided
+    /// record. An empty or missing amount applies to the whole referenced transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - Header data from the CSV file. This is used to dynamically access the record
+    ///   data regardless of column order. It only depends on the column names being known and
+    ///   consistent.
+    /// * `record` - A StringRecord data row optionally containing an amount
+    fn partial_amount_from_record(
+        headers: &csv::StringRecord,
+        record: &csv::StringRecord,
+    ) -> Result<Option<Decimal>, ParseError> {
+        let amount_opt = match headers.iter().position(|x| x == "amount") {
+            Some(index) => record.get(index),
+            None => None,
+        };
+        match amount_opt {
+            Some(value) if !value.is_empty() => match Decimal::from_str(value) {
+                Ok(decimal) if decimal > Decimal::zero() => Ok(Some(decimal)),
+                Ok(_) => Err(ParseError::new(
+                    ParseErrorKind::BadAmount,
+                    "Partial amounts must be positive.",
+                )),
+                Err(_) => Err(ParseError::new(
+                    ParseErrorKind::BadAmount,
+                    "Failed to parse partial amount.",
+                )),
+            },
+            _ => Ok(None),
+        }
+    }
 }
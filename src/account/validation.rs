@@ -0,0 +1,538 @@
+use super::{
+    Identifier, Ledger, OpeningBalance, ParseError, ParseErrorKind, Policy, Transaction,
+    TransactionType,
+};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Columns a transaction row must have.
+const REQUIRED_COLUMNS: [&str; 3] = ["type", "client", "tx"];
+
+/// Every column a transaction row may have.
+const KNOWN_COLUMNS: [&str; 10] = [
+    "type",
+    "client",
+    "tx",
+    "amount",
+    "timestamp",
+    "currency",
+    "destination",
+    "reference",
+    "operator",
+    "reason",
+];
+
+/// Kinds of problems found in input data.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// The header or the shape of a row does not match the expected layout.
+    Schema,
+    /// The row names a transaction type that does not exist.
+    UnknownType,
+    /// The row's amount is missing, malformed or negative.
+    BadAmount,
+    /// Another field of the row cannot be parsed.
+    InvalidRow,
+    /// The row reuses the transaction identifier of an earlier row of the same account, which the
+    /// engine rejects.
+    DuplicateTransaction,
+    /// The row reuses the transaction identifier of an earlier row of another client. This is a
+    /// lint: the engine only requires identifiers to be unique within an account and accepts the
+    /// row, but identifiers are expected to be unique across the whole file.
+    ReusedIdentifier,
+    /// The row references a transaction that no earlier row created.
+    UnknownReference,
+    /// The row references a transaction that belongs to a different client.
+    CrossClientReference,
+    /// The row is valid but would be rejected under the current Policy.
+    Rejected,
+    /// Applying the row in the dry run broke a balance invariant.
+    InvariantViolation,
+}
+
+impl IssueKind {
+    /// Indicates whether the kind flags data the engine accepts as it is.
+    pub fn is_lint(&self) -> bool {
+        matches!(self, IssueKind::ReusedIdentifier)
+    }
+
+    /// Name of the kind as it appears in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            IssueKind::Schema => "schema",
+            IssueKind::UnknownType => "unknown_type",
+            IssueKind::BadAmount => "bad_amount",
+            IssueKind::InvalidRow => "invalid_row",
+            IssueKind::DuplicateTransaction => "duplicate_transaction",
+            IssueKind::ReusedIdentifier => "reused_identifier",
+            IssueKind::UnknownReference => "unknown_reference",
+            IssueKind::CrossClientReference => "cross_client_reference",
+            IssueKind::Rejected => "rejected",
+            IssueKind::InvariantViolation => "invariant_violation",
+        }
+    }
+}
+
+/// A single problem found in input data.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Issue {
+    /// Line of the input the problem was found on, starting at 1 for the header.
+    pub line: u64,
+    /// What kind of problem it is.
+    pub kind: IssueKind,
+    /// Description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: {}: {}",
+            self.line,
+            self.kind.name(),
+            self.message
+        )
+    }
+}
+
+/// The result of checking input data without rendering any balances. Rows are parsed with the
+/// same rules as `Input::from_csv_data` and applied in file order to a Ledger that is then
+/// discarded.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Validation {
+    /// Number of data rows read, not counting the header.
+    pub rows: u64,
+    /// Number of rows that parsed into a transaction.
+    pub transactions: u64,
+    /// Every problem found, in input order.
+    pub issues: Vec<Issue>,
+}
+
+/// Where a transaction identifier was used by a row that creates a transaction.
+#[derive(Debug, Clone)]
+struct Origin {
+    line: u64,
+    client: Identifier,
+    destination: Option<Identifier>,
+}
+
+impl Origin {
+    /// Indicates whether the transaction belongs to the account of the client, as its owner or
+    /// as the destination of a transfer.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client identifier
+    fn involves(&self, client: &Identifier) -> bool {
+        self.client == *client || self.destination.as_ref() == Some(client)
+    }
+
+    /// Where the transaction identifier was first used: a line of the input, or the opening
+    /// balances, which are recorded at line zero.
+    fn location(&self) -> String {
+        match self.line {
+            0 => String::from("the opening balances"),
+            line => format!("line {}", line),
+        }
+    }
+}
+
+impl Validation {
+    /// Checks CSV data for schema problems, rows that cannot be parsed, duplicate transaction
+    /// identifiers, references to missing or foreign transactions, and rows the Policy would
+    /// reject. The dry run seeds accounts with the opening balances before the first row.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Reference to a Vec<u8> of CSV data.
+    /// * `policy` - The rules the dry run applies transactions under.
+    /// * `balances` - The opening balances of the accounts.
+    /// * `paranoid` - Whether the dry run checks balance invariants in release builds.
+    pub fn from_csv_data(
+        data: &[u8],
+        policy: &Arc<Policy>,
+        balances: &[OpeningBalance],
+        paranoid: bool,
+    ) -> Validation {
+        let mut validation = Validation::default();
+        let mut csv_results = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(data);
+        let headers = match csv_results.headers() {
+            Ok(headers) => headers.clone(),
+            Err(error) => {
+                validation.issue(
+                    1,
+                    IssueKind::Schema,
+                    format!("Unreadable header. {}", error),
+                );
+                return validation;
+            }
+        };
+        let missing: Vec<&str> = REQUIRED_COLUMNS
+            .iter()
+            .filter(|column| !headers.iter().any(|header| header == **column))
+            .copied()
+            .collect();
+        if !missing.is_empty() {
+            validation.issue(
+                1,
+                IssueKind::Schema,
+                format!("Missing required columns: {}.", missing.join(", ")),
+            );
+            return validation;
+        }
+        for header in headers.iter() {
+            if !KNOWN_COLUMNS.contains(&header) {
+                validation.issue(
+                    1,
+                    IssueKind::Schema,
+                    format!("Unknown column \"{}\" is ignored.", header),
+                );
+            }
+        }
+
+        let mut origins: HashMap<Identifier, Vec<Origin>> = HashMap::new();
+        let mut ledger = Ledger::new(policy.clone()).paranoid(paranoid);
+        for balance in balances {
+            if let Some(tx) = &balance.held_tx {
+                origins.insert(
+                    tx.clone(),
+                    vec![Origin {
+                        line: 0,
+                        client: balance.client.clone(),
+                        destination: None,
+                    }],
+                );
+            }
+            ledger.apply(balance.transaction());
+        }
+        for result in csv_results.records() {
+            validation.rows += 1;
+            let record = match result {
+                Ok(record) => record,
+                Err(error) => {
+                    let line = error.position().map_or(0, |position| position.line());
+                    validation.issue(line, IssueKind::Schema, error.to_string());
+                    continue;
+                }
+            };
+            let line = record.position().map_or(0, |position| position.line());
+            let transaction = match Transaction::from_record(&headers, &record) {
+                Ok(transaction) => transaction,
+                Err(error) => {
+                    let kind = Validation::classify(&error);
+                    let message = match (kind, headers.iter().position(|x| x == "type")) {
+                        (IssueKind::UnknownType, Some(index)) => format!(
+                            "Unknown transaction type \"{}\".",
+                            record.get(index).unwrap_or_default()
+                        ),
+                        _ => error.message,
+                    };
+                    validation.issue(line, kind, message);
+                    continue;
+                }
+            };
+            validation.transactions += 1;
+
+            let issues = validation.issues.len();
+            let used = origins.get(&transaction.tx).map_or(&[][..], Vec::as_slice);
+            if Validation::references(&transaction.transaction_type) {
+                match used.first() {
+                    None => validation.issue(
+                        line,
+                        IssueKind::UnknownReference,
+                        format!(
+                            "References tx {}, which no earlier row created.",
+                            transaction.tx
+                        ),
+                    ),
+                    Some(origin)
+                        if !used
+                            .iter()
+                            .any(|origin| origin.involves(&transaction.client)) =>
+                    {
+                        validation.issue(
+                            line,
+                            IssueKind::CrossClientReference,
+                            format!(
+                                "References tx {} of client {} on {}.",
+                                transaction.tx,
+                                origin.client,
+                                origin.location()
+                            ),
+                        )
+                    }
+                    Some(_) => (),
+                }
+            } else if let Some(origin) = used
+                .iter()
+                .find(|origin| origin.involves(&transaction.client))
+            {
+                validation.issue(
+                    line,
+                    IssueKind::DuplicateTransaction,
+                    format!("Reuses tx {} from {}.", transaction.tx, origin.location()),
+                );
+            } else {
+                // The engine accepts an identifier reused by another client, so the row is still
+                // recorded as an origin for later references.
+                if let Some(origin) = used.first() {
+                    validation.issue(
+                        line,
+                        IssueKind::ReusedIdentifier,
+                        format!(
+                            "Reuses tx {} of client {} from {}.",
+                            transaction.tx,
+                            origin.client,
+                            origin.location()
+                        ),
+                    );
+                }
+                let destination = match &transaction.transaction_type {
+                    TransactionType::Transfer { destination, .. } => Some(destination.clone()),
+                    _ => None,
+                };
+                origins
+                    .entry(transaction.tx.clone())
+                    .or_default()
+                    .push(Origin {
+                        line,
+                        client: transaction.client.clone(),
+                        destination,
+                    });
+            }
+
+            // Rows already reported above are usually rejected for the same reason, unless they
+            // were only reported by a lint.
+            let client = transaction.client.clone();
+            let violations = ledger.violations().len();
+            let rejection = ledger
+                .apply(transaction)
+                .into_iter()
+                .find(|step| !step.automatic && step.client == client)
+                .and_then(|step| step.outcome.err());
+            let reported = validation.issues[issues..]
+                .iter()
+                .any(|issue| !issue.kind.is_lint());
+            if let (Some(rejection), false) = (rejection, reported) {
+                validation.issue(line, IssueKind::Rejected, format!("{}.", rejection));
+            }
+            for violation in &ledger.violations()[violations..] {
+                validation.issue(line, IssueKind::InvariantViolation, violation.to_string());
+            }
+        }
+        validation
+    }
+
+    /// Indicates whether the data passed every check.
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Writes a human-readable summary followed by every issue into a provided target that
+    /// implements the `Write` trait.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Anything that implements the Write trait.
+    pub fn write_text(&self, mut writer: impl std::io::Write) -> Result<()> {
+        writeln!(
+            writer,
+            "Checked {} rows: {} transactions, {} issues.",
+            self.rows,
+            self.transactions,
+            self.issues.len()
+        )
+        .context("Writer failed to write validation summary.")?;
+        for issue in &self.issues {
+            writeln!(writer, "{}", issue).context("Writer failed to write validation issue.")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the validation as JSON into a provided target that implements the `Write` trait.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Anything that implements the Write trait.
+    pub fn write_json(&self, mut writer: impl std::io::Write) -> Result<()> {
+        serde_json::to_writer_pretty(&mut writer, self)
+            .context("Failed to serialize validation to JSON.")?;
+        writeln!(writer).context("Writer failed to write validation.")
+    }
+
+    /// Records an issue.
+    fn issue(&mut self, line: u64, kind: IssueKind, message: String) {
+        self.issues.push(Issue {
+            line,
+            kind,
+            message,
+        });
+    }
+
+    /// The kind of issue reported for a row that could not be parsed.
+    fn classify(error: &ParseError) -> IssueKind {
+        match error.kind {
+            ParseErrorKind::UnknownType => IssueKind::UnknownType,
+            ParseErrorKind::BadAmount => IssueKind::BadAmount,
+            ParseErrorKind::InvalidRow => IssueKind::InvalidRow,
+        }
+    }
+
+    /// Indicates whether a transaction refers to an earlier transaction by its identifier rather
+    /// than creating one.
+    fn references(transaction_type: &TransactionType) -> bool {
+        matches!(
+            transaction_type,
+            TransactionType::Dispute(_)
+                | TransactionType::Resolve(_)
+                | TransactionType::Chargeback(_)
+                | TransactionType::Capture(_)
+                | TransactionType::Void
+        )
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(data: &[u8]) -> Validation {
+        Validation::from_csv_data(data, &Arc::new(Policy::default()), &[], true)
+    }
+
+    fn kinds(validation: &Validation) -> Vec<(u64, IssueKind)> {
+        validation
+            .issues
+            .iter()
+            .map(|issue| (issue.line, issue.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_validation() {
+        let validation = validate(
+            b"type,client,tx,amount,note\n\
+              deposit,1,1,10.0,\n\
+              refund,1,2,1.0,\n\
+              deposit,1,3,ten,\n\
+              deposit,1,1,5.0,\n\
+              deposit,2,4\n\
+              dispute,1,9,,\n\
+              dispute,2,1,,\n\
+              withdraw,1,5,50.0,\n\
+              deposit,,6,1.0,\n\
+              dispute,1,1,,\n",
+        );
+        assert_eq!(validation.rows, 10);
+        assert_eq!(validation.transactions, 6);
+        assert_eq!(
+            kinds(&validation),
+            vec![
+                (1, IssueKind::Schema),
+                (3, IssueKind::UnknownType),
+                (4, IssueKind::BadAmount),
+                (5, IssueKind::DuplicateTransaction),
+                (6, IssueKind::Schema),
+                (7, IssueKind::UnknownReference),
+                (8, IssueKind::CrossClientReference),
+                (9, IssueKind::Rejected),
+                (10, IssueKind::InvalidRow),
+            ]
+        );
+        assert_eq!(
+            validation.issues[7].to_string(),
+            "line 9: rejected: insufficient available funds."
+        );
+        assert!(!validation.is_valid());
+    }
+
+    #[test]
+    fn test_validation_schema() {
+        let validation = validate(b"kind,client,amount\ndeposit,1,1.0\n");
+        assert_eq!(kinds(&validation), vec![(1, IssueKind::Schema)]);
+        assert_eq!(
+            validation.issues[0].message,
+            "Missing required columns: type, tx."
+        );
+
+        let validation = validate(b"type,client,tx,amount\ndeposit,1,1,1.0\ndispute,1,1,\n");
+        assert!(validation.is_valid());
+        assert_eq!(validation.transactions, 2);
+    }
+
+    #[test]
+    fn test_validation_reused_identifier() {
+        // The engine accepts a transaction identifier reused by another client, so the reuse is
+        // only a lint and disputes of either transaction are valid.
+        let validation = validate(
+            b"type,client,tx,amount\n\
+              deposit,1,1,10.0\n\
+              deposit,2,1,7.0\n\
+              dispute,2,1,\n\
+              dispute,1,1,\n\
+              deposit,2,1,1.0\n\
+              dispute,3,1,\n",
+        );
+        assert_eq!(
+            kinds(&validation),
+            vec![
+                (3, IssueKind::ReusedIdentifier),
+                (6, IssueKind::DuplicateTransaction),
+                (7, IssueKind::CrossClientReference),
+            ]
+        );
+        assert_eq!(
+            validation.issues[0].to_string(),
+            "line 3: reused_identifier: Reuses tx 1 of client 1 from line 2."
+        );
+        assert_eq!(validation.issues[1].message, "Reuses tx 1 from line 3.");
+        assert!(IssueKind::ReusedIdentifier.is_lint());
+
+        // A lint does not hide a rejection of the same row.
+        let validation = validate(
+            b"type,client,tx,amount,currency\n\
+              deposit,1,1,10.0,usd\n\
+              deposit,2,1,7.0,eur\n",
+        );
+        assert_eq!(
+            kinds(&validation),
+            vec![(3, IssueKind::ReusedIdentifier), (3, IssueKind::Rejected)]
+        );
+    }
+
+    #[test]
+    fn test_validation_parse_errors() {
+        let validation = validate(
+            b"type,client,tx,amount,destination,reference\n\
+              deposit,1,1,10.0,,\n\
+              transfer,1,2,1.0,,\n\
+              dispute,1,1,-1.0,,\n\
+              adjustment,1,3,1.0,,\n\
+              adjustment,1,4,,,ledger fix\n\
+              unlock,1,5,,,\n",
+        );
+        assert_eq!(
+            kinds(&validation),
+            vec![
+                (3, IssueKind::InvalidRow),
+                (4, IssueKind::BadAmount),
+                (5, IssueKind::InvalidRow),
+                (6, IssueKind::BadAmount),
+                (7, IssueKind::InvalidRow),
+            ]
+        );
+        assert_eq!(
+            validation.issues[2].message,
+            "Adjustments require a reference."
+        );
+    }
+}
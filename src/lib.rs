@@ -0,0 +1,2 @@
+//! Renders transaction data into Account states.
+pub mod account;
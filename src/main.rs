@@ -1,12 +1,18 @@
 use anyhow::{Context, Result};
-use env_logger;
-use log::trace;
-use std::path::PathBuf;
+use log::{trace, warn};
+use rust_decimal::Decimal;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::string::ParseError;
+use std::sync::Arc;
+use structopt::clap::AppSettings;
 use structopt::StructOpt;
-mod account;
-use account::Account;
+use toy_engine::account::{
+    Account, AsOf, Conversion, Currency, ExpectedBalances, Generator, GeneratorOptions, Identifier,
+    Input, InputOptions, OpeningBalance, OutputOptions, Policy, RateTable, RejectedTransaction,
+    RiskEvent, Validation,
+};
 
 /// Optional input data format specifier.
 #[derive(Debug, PartialEq, StructOpt)]
@@ -27,40 +33,375 @@ impl FromStr for SourceType {
     }
 }
 
+/// Report format of the `validate` subcommand.
+#[derive(Debug, PartialEq)]
+enum ReportFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!(
+                "Unknown report format \"{}\". Use text or json.",
+                s
+            )),
+        }
+    }
+}
+
+// Subcommands that inspect or produce input rather than rendering every account.
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Print a single account's timeline: each transaction, whether it was applied or rejected
+    /// and why, and the running balance after it
+    Explain {
+        /// Client identifier of the account to explain
+        #[structopt(long)]
+        client: Identifier,
+        /// Input identifier (CSV file path by default)
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+    },
+    /// Check an input file for problems without rendering balances, failing if any are found
+    Validate {
+        /// Report format, either `text` or `json`
+        #[structopt(long, default_value = "text")]
+        format: ReportFormat,
+        /// Input identifier (CSV file path by default)
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+    },
+    /// Compare the balances rendered from an input file with expected closing balances, writing
+    /// every difference and failing if any are found
+    Reconcile {
+        /// CSV file of expected balances in the same shape as the account output
+        #[structopt(long, parse(from_os_str))]
+        expected: PathBuf,
+        /// The largest difference between amounts that still counts as a match
+        #[structopt(long, default_value = "0")]
+        tolerance: Decimal,
+        /// Input identifier (CSV file path by default)
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+    },
+    /// Write synthetic transaction CSV for load testing and demos
+    Generate {
+        /// Number of distinct clients
+        #[structopt(long, default_value = "100")]
+        clients: u64,
+        /// Number of rows to write
+        #[structopt(long, default_value = "10000")]
+        rows: u64,
+        /// Probability of a row disputing an earlier deposit
+        #[structopt(long, default_value = "0.02")]
+        dispute_rate: f64,
+        /// Probability of a row resolving an open dispute
+        #[structopt(long, default_value = "0.01")]
+        resolve_rate: f64,
+        /// Probability of a row charging back an open dispute
+        #[structopt(long, default_value = "0.005")]
+        chargeback_rate: f64,
+        /// Probability of a row that cannot be parsed
+        #[structopt(long, default_value = "0")]
+        malformed_rate: f64,
+        /// Probability of a row repeating a recent deposit or withdrawal
+        #[structopt(long, default_value = "0")]
+        duplicate_rate: f64,
+        /// Seed of the random number generator
+        #[structopt(long, default_value = "0")]
+        seed: u64,
+        /// Also write the account states the generated data should produce to this CSV file
+        #[structopt(long, parse(from_os_str))]
+        expected: Option<PathBuf>,
+    },
+}
+
 /// Data structure used in parsing of command line arguments
 #[derive(Debug, StructOpt)]
-#[structopt(name = "Toy Engine", about = "Parse CSV path")]
+#[structopt(
+    name = "Toy Engine",
+    about = "Parse CSV path",
+    setting = AppSettings::ArgsNegateSubcommands
+)]
 struct Arguments {
     /// Input identifier (CSV file path by default)
     #[structopt(parse(from_os_str))]
-    input: std::path::PathBuf,
+    input: Option<PathBuf>,
     /// Output file path (defaults to `stdout` if not present)
-    #[structopt(short, long, parse(from_os_str))]
+    #[structopt(short, long, parse(from_os_str), global = true)]
     output: Option<PathBuf>,
     /// Source data type (defaults to CSV file input if not specified)
     #[structopt(short, long)]
     source_type: Option<SourceType>,
+    /// Render account states as they stood at a point in the input (`tx:<id>`, `line:<number>` or
+    /// a timestamp)
+    #[structopt(long, global = true)]
+    as_of: Option<AsOf>,
+    /// Reorder transactions by their `timestamp` column, tolerating rows that arrive up to this
+    /// many seconds behind the latest timestamp seen
+    #[structopt(long, global = true)]
+    lateness: Option<u32>,
+    /// Write rows that arrive outside the lateness window to this CSV file instead of discarding
+    /// them
+    #[structopt(long, parse(from_os_str), global = true)]
+    quarantine: Option<PathBuf>,
+    /// Add columns totalling each account's applied transactions by category
+    #[structopt(long)]
+    breakdown: bool,
+    /// Add `closed`, `negative` and `flags` columns describing each account's status
+    #[structopt(long)]
+    status: bool,
+    /// Convert each client's balances in every currency into this currency and combine them into
+    /// a single row
+    #[structopt(long, requires = "fx-rates")]
+    convert_to: Option<Currency>,
+    /// CSV file of exchange rates with `from`, `to` and `rate` columns, used by `--convert-to`
+    #[structopt(long, parse(from_os_str))]
+    fx_rates: Option<PathBuf>,
+    /// Check balance invariants after every applied transaction, failing on the first broken
+    /// balance (always on in debug builds)
+    #[structopt(long, global = true)]
+    paranoid: bool,
+    /// Write every firing of a risk rule to this CSV file for review
+    #[structopt(long, parse(from_os_str))]
+    risk_log: Option<PathBuf>,
+    /// Write every rejected transaction and the reason it was rejected to this CSV file
+    #[structopt(long, parse(from_os_str))]
+    rejections: Option<PathBuf>,
+    /// CSV or JSON file of balances to seed accounts with before any transaction is applied (JSON
+    /// if the file name ends in `.json`)
+    #[structopt(long, parse(from_os_str), global = true)]
+    opening_balances: Option<PathBuf>,
+    /// YAML file configuring the rules accounts apply (defaults apply if not present)
+    #[structopt(long, parse(from_os_str), global = true)]
+    policy: Option<PathBuf>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+/// Creates the file at the provided path, or falls back to `stdout` if there is none.
+fn create_writer(path: Option<&PathBuf>) -> Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(
+            std::fs::File::create(path)
+                .with_context(|| format!("Failed to create file {:?}", path))?,
+        ),
+        None => Box::new(std::io::stdout()),
+    })
+}
+
+/// Loads the Policy from the provided path, or falls back to the default Policy if there is none.
+fn read_policy(path: Option<&PathBuf>) -> Result<Arc<Policy>> {
+    Ok(Arc::new(match path {
+        Some(path) => Policy::from_yaml(
+            &std::fs::read(path).with_context(|| format!("Failed to read file {:?}", path))?,
+        )?,
+        None => Policy::default(),
+    }))
+}
+
+/// Builds the output options from the command line, loading the exchange rates if a conversion
+/// was requested.
+fn output_options(args: &Arguments) -> Result<OutputOptions> {
+    let conversion = match (&args.convert_to, &args.fx_rates) {
+        (Some(currency), Some(path)) => Some(Conversion {
+            currency: currency.clone(),
+            rates: RateTable::from_csv_data(
+                &std::fs::read(path).with_context(|| format!("Failed to read file {:?}", path))?,
+            )?,
+        }),
+        _ => None,
+    };
+    Ok(OutputOptions {
+        breakdown: args.breakdown,
+        status: args.status,
+        conversion,
+        paranoid: args.paranoid,
+    })
+}
+
+/// Reads transactions from the provided input path according to the command line options.
+/// Rows that arrive outside the lateness window are written to the quarantine file, if any, and
+/// opening balances are placed before every other transaction.
+fn read_input(path: &Path, args: &Arguments) -> Result<Input> {
+    trace!("Reading data from provided path.");
+    let data = std::fs::read(path).with_context(|| format!("Failed to read file {:?}", path))?;
+    let options = InputOptions {
+        as_of: args.as_of.clone(),
+        lateness: args
+            .lateness
+            .map(|seconds| chrono::Duration::seconds(seconds.into())),
+    };
+    let input = Input::from_csv_data(&data, &options)
+        .context("TransactionSet failed generation from the provided data")?;
+    if !input.late.is_empty() {
+        match &args.quarantine {
+            Some(quarantine) => input.write_late(create_writer(Some(quarantine))?)?,
+            None => warn!(
+                "Discarded {} rows outside the lateness window.",
+                input.late.len()
+            ),
+        }
+    }
+    Ok(input.with_opening_balances(&read_opening_balances(args)?))
+}
+
+/// Loads the opening balances from the path on the command line, if there is one.
+fn read_opening_balances(args: &Arguments) -> Result<Vec<OpeningBalance>> {
+    match &args.opening_balances {
+        Some(path) => {
+            let data =
+                std::fs::read(path).with_context(|| format!("Failed to read file {:?}", path))?;
+            match path.extension() {
+                Some(extension) if extension == "json" => OpeningBalance::from_json_data(&data),
+                _ => OpeningBalance::from_csv_data(&data),
+            }
+        }
+        None => Ok(Vec::new()),
+    }
 }
 
 fn main() -> Result<()> {
     env_logger::init();
     trace!("Parsing command line arguments.");
     let args = Arguments::from_args();
-    trace!("Reading data from provided path.");
-    let transactions_data = std::fs::read(&args.input)
-        .with_context(|| format!("Failed to read file {:?}", &args.input))?;
+    let policy = read_policy(args.policy.as_ref())?;
+
+    // The output file is only created once the input has been read, so that pointing --output
+    // at the input does not truncate it first.
+    if let Some(command) = &args.command {
+        return match command {
+            Command::Explain { client, input } => {
+                let input = read_input(input, &args)?;
+                Account::statement_from_input(
+                    input,
+                    client.clone(),
+                    &policy,
+                    args.paranoid,
+                    create_writer(args.output.as_ref())?,
+                )
+            }
+            Command::Validate { format, input } => {
+                if args.as_of.is_some() || args.lateness.is_some() || args.quarantine.is_some() {
+                    anyhow::bail!(
+                        "Validate checks every row in file order, so it cannot be used with \
+                         --as-of, --lateness or --quarantine."
+                    );
+                }
+                let data = std::fs::read(input)
+                    .with_context(|| format!("Failed to read file {:?}", input))?;
+                let validation = Validation::from_csv_data(
+                    &data,
+                    &policy,
+                    &read_opening_balances(&args)?,
+                    args.paranoid,
+                );
+                let writer = create_writer(args.output.as_ref())?;
+                match format {
+                    ReportFormat::Text => validation.write_text(writer)?,
+                    ReportFormat::Json => validation.write_json(writer)?,
+                }
+                if !validation.is_valid() {
+                    anyhow::bail!("Found {} issues.", validation.issues.len());
+                }
+                Ok(())
+            }
+            Command::Reconcile {
+                expected,
+                tolerance,
+                input,
+            } => {
+                let expected = ExpectedBalances::from_csv_data(
+                    &std::fs::read(expected)
+                        .with_context(|| format!("Failed to read file {:?}", expected))?,
+                )?;
+                let input = read_input(input, &args)?;
+                let discrepancies = Account::reconcile_input(
+                    input,
+                    &policy,
+                    &expected,
+                    *tolerance,
+                    args.paranoid,
+                    create_writer(args.output.as_ref())?,
+                )?;
+                if !discrepancies.is_empty() {
+                    anyhow::bail!("Found {} discrepancies.", discrepancies.len());
+                }
+                Ok(())
+            }
+            Command::Generate {
+                clients,
+                rows,
+                dispute_rate,
+                resolve_rate,
+                chargeback_rate,
+                malformed_rate,
+                duplicate_rate,
+                seed,
+                expected,
+            } => {
+                let generator = Generator::new(GeneratorOptions {
+                    clients: *clients,
+                    rows: *rows,
+                    dispute_rate: *dispute_rate,
+                    resolve_rate: *resolve_rate,
+                    chargeback_rate: *chargeback_rate,
+                    malformed_rate: *malformed_rate,
+                    duplicate_rate: *duplicate_rate,
+                    seed: *seed,
+                })?;
+                let mut data = Vec::new();
+                generator.write_csv(&mut data)?;
+                let mut writer = create_writer(args.output.as_ref())?;
+                writer
+                    .write_all(&data)
+                    .context("Writer failed to write generated data.")?;
+                match expected {
+                    Some(expected) => Account::accounts_state_from_input(
+                        Input::from_csv_data(&data, &InputOptions::default())?,
+                        &policy,
+                        &OutputOptions::default(),
+                        create_writer(Some(expected))?,
+                    )
+                    .map(|_| ()),
+                    None => Ok(()),
+                }
+            }
+        };
+    }
+    let input = args.input.as_ref().context("No input file provided")?;
 
     // CsvFile is the only supported variant at the moment, but the design can be
     // easily extended.
-    if let Some(source_type) = args.source_type {
-        match source_type {
-            SourceType::CsvFile => {
-                Account::accounts_state_from_csv_data(&transactions_data, &mut std::io::stdout())
+    match args.source_type.as_ref().unwrap_or(&SourceType::CsvFile) {
+        SourceType::CsvFile => {
+            let input = read_input(input, &args)?;
+            let report = Account::accounts_state_from_input(
+                input,
+                &policy,
+                &output_options(&args)?,
+                create_writer(args.output.as_ref())?,
+            )?;
+            if let Some(rejections) = &args.rejections {
+                RejectedTransaction::write_csv(
+                    &report.rejections,
+                    create_writer(Some(rejections))?,
+                )?;
+            }
+            match &args.risk_log {
+                Some(risk_log) => {
+                    RiskEvent::write_csv(&report.risk_events, create_writer(Some(risk_log))?)
+                }
+                None => Ok(()),
             }
-            _ => Ok(()),
         }
-    } else {
-        Account::accounts_state_from_csv_data(&transactions_data, &mut std::io::stdout())
+        _ => Ok(()),
     }
 }
 
@@ -86,4 +427,386 @@ mod tests {
             .stderr(predicate::str::contains("No such file or directory"));
         Ok(())
     }
+
+    #[test]
+    fn as_of_line() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("test_data/sample_input.csv")
+            .arg("--as-of")
+            .arg("line:6");
+        cmd.assert().success().stdout(predicate::eq(
+            "client,available,held,total,locked\n1,2.0,0.0,2.0,false\n2,2.0,0.0,2.0,false\n3,2.0,0.0,2.0,false\n4,5.5454540,0.0,5.5454540,false\n\n",
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn as_of_tx() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("test_data/sample_input.csv")
+            .arg("--as-of")
+            .arg("tx:4");
+        cmd.assert().success().stdout(predicate::eq(
+            "client,available,held,total,locked\n1,0.5,0.0,0.5,false\n2,2.0,0.0,2.0,false\n3,2.0,0.0,2.0,false\n4,5.5454540,0.0,5.5454540,false\n\n",
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn as_of_invalid() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("test_data/sample_input.csv")
+            .arg("--as-of")
+            .arg("yesterday");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Unrecognized as-of point"));
+        Ok(())
+    }
+
+    #[test]
+    fn explain_client() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("explain")
+            .arg("--client")
+            .arg("2")
+            .arg("test_data/sample_input.csv");
+        cmd.assert().success().stdout(predicate::eq(
+            "tx,timestamp,type,amount,currency,outcome,reason,available,held,total,locked\n\
+             2,,deposit,2.0,,applied,,2.0,0.0,2.0,false\n\
+             5,,withdraw,3.0,,rejected,insufficient available funds,2.0,0.0,2.0,false\n\
+             2,,dispute,,,applied,,0.0,2.0,2.0,false\n\
+             2,,chargeback,,,applied,,0.0,0.0,0.0,true\n",
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn lateness_quarantine() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let directory = tempfile::tempdir()?;
+        let quarantine = directory.path().join("quarantine.csv");
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("test_data/timestamped_input.csv")
+            .arg("--lateness")
+            .arg("60")
+            .arg("--quarantine")
+            .arg(&quarantine);
+        cmd.assert().success().stdout(predicate::eq(
+            "client,available,held,total,locked\n1,8.0,0.0,8.0,false\n\n",
+        ));
+        assert_eq!(
+            std::fs::read_to_string(&quarantine)?,
+            "type,client,tx,amount,timestamp\ndeposit,1,5,100.0,2021-01-01T00:00:20Z\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn output_over_input() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let directory = tempfile::tempdir()?;
+        let path = directory.path().join("input.csv");
+        std::fs::copy("test_data/sample_input.csv", &path)?;
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg(&path).arg("-o").arg(&path);
+        cmd.assert().success();
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            "client,available,held,total,locked\n\
+             1,0.5,0.0,0.5,false\n\
+             2,0.0,0.0,0.0,true\n\
+             3,0.0,2.0,2.0,false\n\
+             4,5.5454540,0.0,5.5454540,false\n\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn breakdown_columns() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("test_data/fees_input.csv").arg("--breakdown");
+        cmd.assert().success().stdout(predicate::eq(
+            "client,available,held,total,locked,deposits,withdrawals,transfers_in,transfers_out,fees,interest,adjustments\n\
+             1,7.0,0.0,7.0,false,10.0,0.0,0.0,0.0,2.5,0.5,-1.0\n\n",
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn currency_rows() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("test_data/currency_input.csv");
+        cmd.assert().success().stdout(predicate::eq(
+            "client,currency,available,held,total,locked\n\
+             1,EUR,15.0,0.0,15.0,false\n\
+             1,USD,10.0,0.0,10.0,false\n\
+             2,JPY,0.0,100.0,100.0,false\n\n",
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn convert_to() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("test_data/currency_input.csv")
+            .arg("--convert-to")
+            .arg("usd")
+            .arg("--fx-rates")
+            .arg("test_data/fx_rates.csv");
+        cmd.assert().success().stdout(predicate::eq(
+            "client,currency,available,held,total,locked\n\
+             1,USD,26.50,0.0,26.50,false\n\
+             2,USD,0.0,0.6667,0.6667,false\n\n",
+        ));
+
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("test_data/sample_input.csv")
+            .arg("--convert-to")
+            .arg("usd")
+            .arg("--fx-rates")
+            .arg("test_data/fx_rates.csv");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("without a currency"));
+        Ok(())
+    }
+
+    #[test]
+    fn wide_identifiers() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("test_data/identifier_input.csv");
+        cmd.assert().success().stdout(predicate::eq(
+            "client,available,held,total,locked\n\
+             70000,1.0,0.0,1.0,false\n\
+             18446744073709551615,0.0,10.0,10.0,false\n\
+             007,1.0,0.0,1.0,false\n\
+             18446744073709551616,4.0,0.0,4.0,false\n\
+             3f2b8c1e-6a4d-4e2b-9c1a-7d5e8f0a1b2c,3.0,0.0,3.0,false\n\n",
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn risk_log() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let directory = tempfile::tempdir()?;
+        let risk_log = directory.path().join("risk-log.csv");
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("test_data/risk_input.csv")
+            .arg("--policy")
+            .arg("test_data/risk_policy.yaml")
+            .arg("--status")
+            .arg("--risk-log")
+            .arg(&risk_log);
+        cmd.assert().success().stdout(predicate::eq(
+            "client,available,held,total,locked,closed,negative,flags\n\
+             1,0.0,0.0,0.0,true,false,false,deposit_then_withdrawal\n\
+             2,0.0,0.0,0.0,false,false,false,\n\n",
+        ));
+        assert_eq!(
+            std::fs::read_to_string(&risk_log)?,
+            "client,currency,tx,timestamp,rule,action\n\
+             1,,2,2021-01-01T00:02:00Z,deposit_then_withdrawal,lock\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn generate() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let directory = tempfile::tempdir()?;
+        let data = directory.path().join("generated.csv");
+        let expected = directory.path().join("expected.csv");
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("generate")
+            .args(["--clients", "4", "--rows", "200", "--seed", "42"])
+            .args(["--malformed-rate", "0.05", "--duplicate-rate", "0.05"])
+            .arg("--expected")
+            .arg(&expected)
+            .arg("--output")
+            .arg(&data);
+        cmd.assert().success();
+        let generated = std::fs::read_to_string(&data)?;
+        assert!(generated.starts_with("type,client,tx,amount\n"));
+        assert_eq!(generated.lines().count(), 201);
+
+        let output = Command::cargo_bin("toy-engine")?.arg(&data).output()?;
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout)?,
+            std::fs::read_to_string(&expected)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn validate() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("validate").arg("test_data/invalid_input.csv");
+        cmd.assert()
+            .failure()
+            .stdout(predicate::eq(
+                "Checked 5 rows: 4 transactions, 4 issues.\n\
+                 line 3: unknown_type: Unknown transaction type \"refund\".\n\
+                 line 4: duplicate_transaction: Reuses tx 1 from line 2.\n\
+                 line 5: cross_client_reference: References tx 1 of client 1 on line 2.\n\
+                 line 6: rejected: insufficient available funds.\n",
+            ))
+            .stderr(predicate::str::contains("Found 4 issues."));
+
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("validate")
+            .args(["--format", "json"])
+            .arg("test_data/currency_input.csv");
+        cmd.assert().success().stdout(predicate::str::starts_with(
+            "{\n  \"rows\": 5,\n  \"transactions\": 5,\n  \"issues\": []\n}",
+        ));
+
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("validate")
+            .args(["--opening-balances", "test_data/opening_balances.csv"])
+            .arg("test_data/sample_input.csv");
+        cmd.assert().failure().stdout(predicate::eq(
+            "Checked 13 rows: 13 transactions, 4 issues.\n\
+             line 4: rejected: account is locked.\n\
+             line 8: rejected: insufficient available funds.\n\
+             line 13: cross_client_reference: References tx 2 of client 2 on line 3.\n\
+             line 14: rejected: account is locked.\n",
+        ));
+
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("validate")
+            .args(["--lateness", "60"])
+            .arg("test_data/timestamped_input.csv");
+        cmd.assert()
+            .failure()
+            .stdout(predicate::eq(""))
+            .stderr(predicate::str::contains("cannot be used with --as-of"));
+        Ok(())
+    }
+
+    #[test]
+    fn reconcile() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("reconcile")
+            .args(["--expected", "test_data/sample_output.csv"])
+            .arg("test_data/sample_input.csv");
+        cmd.assert().success().stdout(predicate::eq(
+            "client,currency,field,expected,actual,difference\n",
+        ));
+
+        let directory = tempfile::tempdir()?;
+        let expected = directory.path().join("expected.csv");
+        std::fs::write(
+            &expected,
+            "client,available,held,total,locked\n\
+             1,0.45,0.0,0.45,false\n\
+             2,0.0,0.0,0.0,false\n\
+             3,0.0,2.0,2.0,false\n\
+             5,1.0,0.0,1.0,false\n",
+        )?;
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("reconcile")
+            .arg("--expected")
+            .arg(&expected)
+            .arg("test_data/sample_input.csv");
+        cmd.assert()
+            .failure()
+            .stdout(predicate::eq(
+                "client,currency,field,expected,actual,difference\n\
+                 1,,available,0.45,0.5,0.05\n\
+                 1,,total,0.45,0.5,0.05\n\
+                 2,,locked,false,true,\n\
+                 4,,account,absent,present,\n\
+                 5,,account,present,absent,\n",
+            ))
+            .stderr(predicate::str::contains("Found 5 discrepancies."));
+
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("reconcile")
+            .arg("--expected")
+            .arg(&expected)
+            .args(["--tolerance", "0.05"])
+            .arg("test_data/sample_input.csv");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Found 3 discrepancies."));
+        std::fs::remove_file(expected)?;
+        Ok(())
+    }
+
+    #[test]
+    fn paranoid_subcommands() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.args(["explain", "--paranoid", "--client", "3"])
+            .arg("test_data/sample_input.csv");
+        cmd.assert().success().stdout(predicate::str::starts_with(
+            "tx,timestamp,type,amount,currency,outcome,reason,available,held,total,locked\n",
+        ));
+
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.args(["reconcile", "--paranoid"])
+            .args(["--expected", "test_data/sample_output.csv"])
+            .arg("test_data/sample_input.csv");
+        cmd.assert().success().stdout(predicate::eq(
+            "client,currency,field,expected,actual,difference\n",
+        ));
+
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.args(["validate", "--paranoid"])
+            .arg("test_data/currency_input.csv");
+        cmd.assert()
+            .success()
+            .stdout(predicate::eq("Checked 5 rows: 5 transactions, 0 issues.\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn opening_balances() -> Result<(), Box<dyn std::error::Error>> {
+        init();
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("test_data/sample_input.csv")
+            .args(["--opening-balances", "test_data/opening_balances.csv"]);
+        cmd.assert().success().stdout(predicate::eq(
+            "client,available,held,total,locked\n\
+             1,10.5,2.0,12.5,false\n\
+             2,0.0,0.0,0.0,true\n\
+             3,0.0,0.0,0.0,true\n\
+             4,5.5454540,0.0,5.5454540,false\n\n",
+        ));
+
+        // A resolve naming the held_tx releases the funds held by the opening balance.
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("test_data/opening_hold_input.csv")
+            .args(["--opening-balances", "test_data/opening_balances.csv"]);
+        cmd.assert().success().stdout(predicate::eq(
+            "client,available,held,total,locked\n\
+             1,13.0,0.0,13.0,false\n\
+             3,0.0,0.0,0.0,true\n\n",
+        ));
+
+        let mut cmd = Command::cargo_bin("toy-engine")?;
+        cmd.arg("explain")
+            .args(["--client", "3"])
+            .args(["--opening-balances", "test_data/opening_balances.csv"])
+            .arg("test_data/sample_input.csv");
+        cmd.assert().success().stdout(predicate::str::starts_with(
+            "tx,timestamp,type,amount,currency,outcome,reason,available,held,total,locked\n\
+             ,,opening,,,applied,,0.0,0.0,0.0,true\n\
+             6,,deposit,2.0,,rejected,account is locked,0.0,0.0,0.0,true\n",
+        ));
+        Ok(())
+    }
 }
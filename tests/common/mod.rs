@@ -0,0 +1,90 @@
+//! Generators shared by the property-based tests.
+
+use proptest::prelude::*;
+use rust_decimal::prelude::*;
+use toy_engine::account::{Transaction, TransactionType};
+
+/// The type of a generated row, with amounts in cents. Deposits and withdrawals usually take a
+/// fresh transaction identifier but may reuse one. Dispute-family rows name any earlier
+/// identifier and may carry a partial amount.
+#[derive(Debug, Clone)]
+enum Op {
+    Deposit(i64, Option<u64>),
+    Withdraw(i64, Option<u64>),
+    Dispute(u64, Option<i64>),
+    Resolve(u64, Option<i64>),
+    Chargeback(u64, Option<i64>),
+}
+
+/// Random interleavings of up to three clients' deposits, withdrawals, disputes, resolves and
+/// chargebacks. Dispute-family rows name any earlier transaction identifier, whether or not it
+/// belongs to the same client or can be disputed.
+///
+/// # Arguments
+///
+/// * `rows` - The number of rows is below this.
+/// * `reuse` - The probability that a deposit or withdrawal reuses an earlier identifier.
+/// * `partial` - The probability that a dispute-family row carries a partial amount.
+pub fn transactions(
+    rows: u64,
+    reuse: f64,
+    partial: f64,
+) -> impl Strategy<Value = Vec<Transaction>> {
+    let reuse = sometimes(reuse, 1u64..rows);
+    let partial = sometimes(partial, 1i64..5_000);
+    let op = prop_oneof![
+        4 => (1i64..10_000, reuse.clone()).prop_map(|(cents, tx)| Op::Deposit(cents, tx)),
+        3 => (1i64..10_000, reuse).prop_map(|(cents, tx)| Op::Withdraw(cents, tx)),
+        2 => (1u64..rows, partial.clone()).prop_map(|(tx, cents)| Op::Dispute(tx, cents)),
+        1 => (1u64..rows, partial.clone()).prop_map(|(tx, cents)| Op::Resolve(tx, cents)),
+        1 => (1u64..rows, partial).prop_map(|(tx, cents)| Op::Chargeback(tx, cents)),
+    ];
+    prop::collection::vec((1u64..=3, op), 1..rows as usize).prop_map(|rows| {
+        rows.into_iter()
+            .enumerate()
+            .map(|(index, (client, op))| {
+                let fresh = index as u64 + 1;
+                let cents = |cents: i64| Decimal::new(cents, 2);
+                let (transaction_type, tx) = match op {
+                    Op::Deposit(amount, tx) => {
+                        (TransactionType::Deposit(cents(amount)), tx.unwrap_or(fresh))
+                    }
+                    Op::Withdraw(amount, tx) => (
+                        TransactionType::Withdraw(cents(amount)),
+                        tx.unwrap_or(fresh),
+                    ),
+                    Op::Dispute(tx, amount) => (TransactionType::Dispute(amount.map(cents)), tx),
+                    Op::Resolve(tx, amount) => (TransactionType::Resolve(amount.map(cents)), tx),
+                    Op::Chargeback(tx, amount) => {
+                        (TransactionType::Chargeback(amount.map(cents)), tx)
+                    }
+                };
+                Transaction {
+                    transaction_type,
+                    tx: tx.min(fresh).into(),
+                    client: client.into(),
+                    timestamp: None,
+                    currency: None,
+                }
+            })
+            .collect()
+    })
+}
+
+/// Values of the provided strategy with the provided probability, and None otherwise.
+///
+/// # Arguments
+///
+/// * `probability` - The probability of a value, which may be zero.
+/// * `strategy` - Generates the values.
+fn sometimes<T>(probability: f64, strategy: T) -> BoxedStrategy<Option<T::Value>>
+where
+    T: Strategy + 'static,
+    T::Value: Clone,
+{
+    if probability > 0.0 {
+        prop::option::weighted(probability, strategy).boxed()
+    } else {
+        Just(None).boxed()
+    }
+}
@@ -0,0 +1,82 @@
+mod common;
+mod reference;
+
+use common::transactions;
+use proptest::prelude::*;
+use reference::Model;
+use std::sync::Arc;
+use toy_engine::account::{
+    Account, Input, InputOptions, Ledger, Policy, Transaction, TransactionSet,
+};
+
+/// Renders transactions as CSV in the format accepted by the CLI.
+fn to_csv(transactions: &[Transaction]) -> Vec<u8> {
+    let mut data = String::from("type,client,tx,amount\n");
+    for transaction in transactions {
+        let amount = transaction
+            .transaction_type
+            .amount()
+            .map_or(String::new(), |amount| amount.to_string());
+        data.push_str(&format!(
+            "{},{},{},{}\n",
+            transaction.transaction_type.name(),
+            transaction.client,
+            transaction.tx,
+            amount
+        ));
+    }
+    data.into_bytes()
+}
+
+proptest! {
+    /// Every transaction applied through `resolve_new_transaction` has the same outcome and
+    /// leaves the same balance as in the reference model.
+    #[test]
+    fn engine_matches_model(transactions in transactions(60, 0.1, 0.3)) {
+        let mut model = Model::default();
+        let mut accounts: Vec<Account> = Vec::new();
+        for transaction in &transactions {
+            let index = match accounts.iter().position(|account| *account.client() == transaction.client) {
+                Some(index) => index,
+                None => {
+                    accounts.push(Account::new(transaction.client.clone()));
+                    accounts.len() - 1
+                }
+            };
+            let outcome = accounts[index].resolve_new_transaction(transaction.clone());
+            prop_assert_eq!(outcome, model.apply(transaction), "{:?}", transaction);
+            prop_assert_eq!(
+                accounts[index].balance(),
+                model.balance(&transaction.client),
+                "{:?}",
+                transaction
+            );
+        }
+    }
+
+    /// Rendering TransactionSets, and reading the same transactions from CSV through the Ledger,
+    /// both end with the balances of the reference model.
+    #[test]
+    fn ingestion_matches_model(transactions in transactions(60, 0.1, 0.3)) {
+        let mut model = Model::default();
+        for transaction in &transactions {
+            let _ = model.apply(transaction);
+        }
+
+        for set in TransactionSet::from_transactions(transactions.clone()) {
+            let client = set.client.clone();
+            let account = Account::from_transaction_set(set);
+            prop_assert_eq!(account.balance(), model.balance(&client));
+        }
+
+        let input = Input::from_csv_data(&to_csv(&transactions), &InputOptions::default()).unwrap();
+        prop_assert_eq!(input.transactions.len(), transactions.len());
+        let mut ledger = Ledger::new(Arc::new(Policy::default()));
+        for transaction in input.transactions {
+            ledger.apply(transaction);
+        }
+        for account in ledger.into_accounts() {
+            prop_assert_eq!(account.balance(), model.balance(account.client()));
+        }
+    }
+}
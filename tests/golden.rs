@@ -0,0 +1,89 @@
+//! Runs every case under `tests/golden/` through the binary. Each case is a directory holding an
+//! `input.csv`, the `accounts.csv` expected on stdout, and optionally the `rejections.csv`
+//! expected from `--rejections` and a `policy.yaml` passed with `--policy`.
+//!
+//! Set `UPDATE_GOLDEN=1` to write the actual output over the expected files instead of comparing
+//! them. Review the resulting diff before committing it.
+
+use assert_cmd::prelude::*;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Compares the actual output of a case with its expected file, or replaces the file when
+/// updating.
+fn compare(expected: &Path, actual: &str, update: bool) -> Result<(), String> {
+    if update {
+        return fs::write(expected, actual).map_err(|err| err.to_string());
+    }
+    let wanted = fs::read_to_string(expected).map_err(|err| err.to_string())?;
+    if wanted == actual {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} differs.\n--- expected\n{}\n--- actual\n{}",
+            expected.display(),
+            wanted,
+            actual
+        ))
+    }
+}
+
+/// Runs a single case, returning a description of every mismatch.
+fn run_case(case: &Path, update: bool) -> Result<(), Box<dyn Error>> {
+    let directory = tempfile::tempdir()?;
+    let rejections = directory.path().join("rejections.csv");
+    let expected_rejections = case.join("rejections.csv");
+    let policy = case.join("policy.yaml");
+
+    let mut cmd = Command::cargo_bin("toy-engine")?;
+    cmd.arg(case.join("input.csv"));
+    if expected_rejections.exists() {
+        cmd.arg("--rejections").arg(&rejections);
+    }
+    if policy.exists() {
+        cmd.arg("--policy").arg(&policy);
+    }
+    let output = cmd.output()?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into());
+    }
+
+    compare(
+        &case.join("accounts.csv"),
+        &String::from_utf8(output.stdout)?,
+        update,
+    )?;
+    if expected_rejections.exists() {
+        compare(
+            &expected_rejections,
+            &fs::read_to_string(&rejections)?,
+            update,
+        )?;
+    }
+    Ok(())
+}
+
+#[test]
+fn golden() -> Result<(), Box<dyn Error>> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut cases: Vec<PathBuf> = fs::read_dir(root)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    cases.retain(|case| case.is_dir());
+    cases.sort();
+    assert!(!cases.is_empty(), "No golden cases found.");
+
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let failures: Vec<String> = cases
+        .iter()
+        .filter_map(|case| {
+            run_case(case, update)
+                .err()
+                .map(|err| format!("Case {}: {}", case.display(), err))
+        })
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+    Ok(())
+}
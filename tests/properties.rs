@@ -0,0 +1,100 @@
+mod common;
+
+use common::transactions;
+use proptest::prelude::*;
+use rust_decimal::prelude::*;
+use std::collections::HashMap;
+use toy_engine::account::{
+    Account, Balance, Identifier, Rejection, Transaction, TransactionSet, TransactionType,
+};
+
+/// Applies transactions one by one through `resolve_new_transaction`, one account per client.
+fn apply_one_by_one(
+    transactions: &[Transaction],
+) -> HashMap<Identifier, (Account, Vec<Result<(), Rejection>>)> {
+    let mut accounts: HashMap<Identifier, (Account, Vec<Result<(), Rejection>>)> = HashMap::new();
+    for transaction in transactions {
+        let (account, outcomes) = accounts
+            .entry(transaction.client.clone())
+            .or_insert_with(|| (Account::new(transaction.client.clone()), Vec::new()));
+        outcomes.push(account.resolve_new_transaction(transaction.clone()));
+    }
+    accounts
+}
+
+proptest! {
+    /// The total of every account equals its applied deposits minus its applied withdrawals,
+    /// adjusted by the disputes, resolves and chargebacks that were applied. Disputes of
+    /// deposits only move funds into held, and their chargebacks remove them. Disputes of
+    /// withdrawals add a pending credit, which resolves remove again.
+    #[test]
+    fn conservation(transactions in transactions(40, 0.0, 0.0)) {
+        for (client, (account, outcomes)) in apply_one_by_one(&transactions) {
+            let mut amounts: HashMap<Identifier, TransactionType> = HashMap::new();
+            let history = transactions.iter().filter(|transaction| transaction.client == client);
+            let mut total = Decimal::zero();
+            for (transaction, outcome) in history.zip(outcomes) {
+                if outcome.is_err() {
+                    continue;
+                }
+                let original = amounts.get(&transaction.tx);
+                total += match (&transaction.transaction_type, original) {
+                    (TransactionType::Deposit(amount), _) => *amount,
+                    (TransactionType::Withdraw(amount), _) => -*amount,
+                    (TransactionType::Dispute(_), Some(TransactionType::Withdraw(amount))) => *amount,
+                    (TransactionType::Resolve(_), Some(TransactionType::Withdraw(amount))) => -*amount,
+                    (TransactionType::Chargeback(_), Some(TransactionType::Deposit(amount))) => -*amount,
+                    _ => Decimal::zero(),
+                };
+                if let TransactionType::Deposit(_) | TransactionType::Withdraw(_) =
+                    transaction.transaction_type
+                {
+                    amounts.insert(transaction.tx.clone(), transaction.transaction_type.clone());
+                }
+            }
+            let balance = account.balance();
+            prop_assert_eq!(balance.total, total);
+            prop_assert_eq!(balance.available + balance.held, balance.total);
+            prop_assert!(balance.held >= Decimal::zero());
+        }
+    }
+
+    /// An account is locked exactly when a chargeback has been applied to it, and a locked
+    /// account rejects every later transaction without changing its balance.
+    #[test]
+    fn lock_semantics(transactions in transactions(40, 0.0, 0.0)) {
+        let mut accounts: HashMap<Identifier, Account> = HashMap::new();
+        let mut charged_back: HashMap<Identifier, bool> = HashMap::new();
+        for transaction in transactions {
+            let account = accounts
+                .entry(transaction.client.clone())
+                .or_insert_with(|| Account::new(transaction.client.clone()));
+            let before: Balance = account.balance();
+            let is_chargeback = matches!(transaction.transaction_type, TransactionType::Chargeback(_));
+            let outcome = account.resolve_new_transaction(transaction.clone());
+            if before.locked {
+                prop_assert_eq!(outcome, Err(Rejection::AccountLocked));
+                prop_assert_eq!(account.balance(), before);
+            } else if outcome.is_ok() && is_chargeback {
+                charged_back.insert(transaction.client.clone(), true);
+            }
+            let expected = charged_back.get(&transaction.client).copied().unwrap_or(false);
+            prop_assert_eq!(account.balance().locked, expected);
+        }
+    }
+
+    /// Rendering a client's transactions with `from_transaction_set` gives the same account as
+    /// applying them one by one.
+    #[test]
+    fn rendering_matches_one_by_one(transactions in transactions(40, 0.0, 0.0)) {
+        let mut one_by_one = apply_one_by_one(&transactions);
+        for set in TransactionSet::from_transactions(transactions) {
+            let client = set.client.clone();
+            let rendered = Account::from_transaction_set(set);
+            let (account, _) = one_by_one.remove(&client).unwrap();
+            prop_assert_eq!(rendered.balance(), account.balance());
+            prop_assert_eq!(rendered.transactions().len(), account.transactions().len());
+        }
+        prop_assert!(one_by_one.is_empty());
+    }
+}
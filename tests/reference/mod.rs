@@ -0,0 +1,189 @@
+//! A deliberately naive model of the account rules under the default Policy, used to check the
+//! production engine. It keeps nothing but a log of the applied transactions for each client and
+//! re-scans the whole log to answer every question, so each rule can be read on its own.
+//!
+//! The model covers deposits, withdrawals, disputes, resolves and chargebacks, with or without
+//! partial amounts, in a single currency and without timestamps.
+
+use rust_decimal::prelude::*;
+use std::collections::HashMap;
+use toy_engine::account::{Balance, Identifier, Rejection, Transaction, TransactionType};
+
+/// The kinds of transactions the model understands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Deposit,
+    Withdraw,
+    Dispute,
+    Resolve,
+    Chargeback,
+}
+
+/// An applied transaction with the amount it actually moved. Disputes, resolves and chargebacks
+/// without an amount are recorded with the amount they defaulted to.
+#[derive(Debug, Clone)]
+struct Entry {
+    kind: Kind,
+    tx: Identifier,
+    amount: Decimal,
+}
+
+/// Applied transactions for every client, in the order they were applied.
+#[derive(Debug, Default)]
+pub struct Model {
+    logs: HashMap<Identifier, Vec<Entry>>,
+}
+
+impl Model {
+    /// Applies a transaction to its client, returning the same Rejection the engine should
+    /// return when it cannot be applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction` - A deposit, withdraw, dispute, resolve or chargeback
+    pub fn apply(&mut self, transaction: &Transaction) -> Result<(), Rejection> {
+        let log = self.logs.entry(transaction.client.clone()).or_default();
+        let (kind, amount) = match transaction.transaction_type {
+            TransactionType::Deposit(amount) => (Kind::Deposit, Some(amount)),
+            TransactionType::Withdraw(amount) => (Kind::Withdraw, Some(amount)),
+            TransactionType::Dispute(amount) => (Kind::Dispute, amount),
+            TransactionType::Resolve(amount) => (Kind::Resolve, amount),
+            TransactionType::Chargeback(amount) => (Kind::Chargeback, amount),
+            _ => panic!("The reference model does not cover {:?}", transaction),
+        };
+        if locked(log) {
+            return Err(Rejection::AccountLocked);
+        }
+        let amount = match kind {
+            Kind::Deposit | Kind::Withdraw => {
+                if log.iter().any(|entry| entry.tx == transaction.tx) {
+                    return Err(Rejection::DuplicateTransaction);
+                }
+                let amount = amount.unwrap();
+                if kind == Kind::Withdraw && available(log) - amount < Decimal::zero() {
+                    return Err(Rejection::InsufficientFunds);
+                }
+                amount
+            }
+            Kind::Dispute => {
+                let original =
+                    original(log, &transaction.tx).ok_or(Rejection::UnknownTransaction)?;
+                let disputed = disputed(log, &transaction.tx);
+                let disputable = original.amount - disputed - charged_back(log, &transaction.tx);
+                if disputable <= Decimal::zero() {
+                    return Err(if disputed > Decimal::zero() {
+                        Rejection::AlreadyDisputed
+                    } else {
+                        Rejection::ExceedsDisputableAmount
+                    });
+                }
+                let amount = amount.unwrap_or(disputable);
+                if amount > disputable {
+                    return Err(Rejection::ExceedsDisputableAmount);
+                }
+                amount
+            }
+            Kind::Resolve | Kind::Chargeback => {
+                original(log, &transaction.tx).ok_or(Rejection::UnknownTransaction)?;
+                let disputed = disputed(log, &transaction.tx);
+                if disputed <= Decimal::zero() {
+                    return Err(Rejection::NotDisputed);
+                }
+                let amount = amount.unwrap_or(disputed);
+                if amount > disputed {
+                    return Err(Rejection::ExceedsDisputedAmount);
+                }
+                amount
+            }
+        };
+        log.push(Entry {
+            kind,
+            tx: transaction.tx.clone(),
+            amount,
+        });
+        Ok(())
+    }
+
+    /// The balance of a client's account, worked out from its whole log.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client identifier of the account
+    pub fn balance(&self, client: &Identifier) -> Balance {
+        let empty = Vec::new();
+        let log = self.logs.get(client).unwrap_or(&empty);
+        let available = available(log);
+        let held = held(log);
+        let total = available + held;
+        Balance {
+            available,
+            held,
+            authorized: Decimal::zero(),
+            total,
+            locked: locked(log),
+            closed: false,
+            negative: available < Decimal::zero() || total < Decimal::zero(),
+        }
+    }
+}
+
+/// The deposit or withdrawal a dispute, resolve or chargeback refers to.
+fn original<'a>(log: &'a [Entry], tx: &Identifier) -> Option<&'a Entry> {
+    log.iter()
+        .find(|entry| entry.tx == *tx && matches!(entry.kind, Kind::Deposit | Kind::Withdraw))
+}
+
+/// Sum of the amounts of a kind of transaction referring to a transaction identifier.
+fn sum(log: &[Entry], tx: &Identifier, kind: Kind) -> Decimal {
+    log.iter()
+        .filter(|entry| entry.tx == *tx && entry.kind == kind)
+        .map(|entry| entry.amount)
+        .sum()
+}
+
+/// The amount of a transaction that is currently under dispute.
+fn disputed(log: &[Entry], tx: &Identifier) -> Decimal {
+    sum(log, tx, Kind::Dispute) - sum(log, tx, Kind::Resolve) - sum(log, tx, Kind::Chargeback)
+}
+
+/// The amount of a transaction that has been charged back for good.
+fn charged_back(log: &[Entry], tx: &Identifier) -> Decimal {
+    sum(log, tx, Kind::Chargeback)
+}
+
+/// Any chargeback locks the account, and nothing in the model unlocks it.
+fn locked(log: &[Entry]) -> bool {
+    log.iter().any(|entry| entry.kind == Kind::Chargeback)
+}
+
+/// Whether the transaction a dispute family entry refers to was a deposit.
+fn of_deposit(log: &[Entry], entry: &Entry) -> bool {
+    original(log, &entry.tx).is_some_and(|original| original.kind == Kind::Deposit)
+}
+
+/// Deposits add funds and withdrawals remove them. A dispute of a deposit moves its amount into
+/// held and a resolve moves it back. A withdrawal charged back is credited back to available.
+fn available(log: &[Entry]) -> Decimal {
+    log.iter()
+        .map(|entry| match (entry.kind, of_deposit(log, entry)) {
+            (Kind::Deposit, _) => entry.amount,
+            (Kind::Withdraw, _) => -entry.amount,
+            (Kind::Dispute, true) => -entry.amount,
+            (Kind::Resolve, true) => entry.amount,
+            (Kind::Chargeback, false) => entry.amount,
+            _ => Decimal::zero(),
+        })
+        .sum()
+}
+
+/// Whatever is under dispute is held, whether it is a deposit or a withdrawal held as a pending
+/// credit.
+fn held(log: &[Entry]) -> Decimal {
+    log.iter()
+        .map(|entry| match entry.kind {
+            Kind::Dispute => entry.amount,
+            Kind::Resolve | Kind::Chargeback => -entry.amount,
+            _ => Decimal::zero(),
+        })
+        .sum()
+}